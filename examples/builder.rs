@@ -4,7 +4,6 @@ use halo2_base::gates::builder::GateThreadBuilder;
 use halo2_base::gates::{GateChip, GateInstructions};
 use halo2_base::halo2_proofs::halo2curves::bn256::Fr;
 use halo2_base::utils::ScalarField;
-use halo2_base::AssignedValue;
 #[allow(unused_imports)]
 use halo2_base::{
     Context,
@@ -12,14 +11,14 @@ use halo2_base::{
 };
 use halo2_proofs::arithmetic::Field;
 use halo2_scaffold::scaffold::cmd::Cli;
-use halo2_scaffold::scaffold::run_builder_on_inputs;
+use halo2_scaffold::scaffold::{run_builder_on_inputs, NamedInstances};
 use rand::rngs::OsRng;
 
 // this algorithm takes a public input x, computes x^2 + 72, and outputs the result as public output
 fn some_algorithm_in_zk<F: ScalarField>(
     builder: &mut GateThreadBuilder<F>,
     x: F,
-    make_public: &mut Vec<AssignedValue<F>>,
+    make_public: &mut NamedInstances<F>,
 ) {
     // can still get a Context via:
     let ctx = builder.main(0); // 0 means FirstPhase, don't worry about it
@@ -1,8 +1,8 @@
 use axiom_eth::{keccak::KeccakChip, EthChip, Field};
 use clap::Parser;
 use ethers_core::utils::keccak256;
-use halo2_base::{gates::RangeInstructions, AssignedValue, Context};
-use halo2_scaffold::scaffold::{cmd::Cli, run_eth};
+use halo2_base::{gates::RangeInstructions, Context};
+use halo2_scaffold::scaffold::{cmd::Cli, run_eth_with_dummy, DummyInput, NamedInstances};
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -10,6 +10,13 @@ pub struct CircuitInput {
     pub bytes: Vec<u8>,
 }
 
+// the circuit depends on the number of bytes, so keys generated from this placeholder are for `LEN = 0`
+impl DummyInput for CircuitInput {
+    fn dummy() -> Self {
+        Self { bytes: vec![] }
+    }
+}
+
 /// Input is a fixed number of bytes. THE CIRCUIT DEPENDS ON THE NUMBER OF BYTES.
 /// The bytes are range checked to be 8 bits each and exposed as public inputs.
 /// The 32 byte keccak hash of the input bytes are exposed as public outputs.
@@ -20,7 +27,7 @@ fn compute_fixed_len_keccak<F: Field>(
     eth_chip: &EthChip<F>,
     keccak: &mut KeccakChip<F>,
     input: CircuitInput,
-    make_public: &mut Vec<AssignedValue<F>>,
+    make_public: &mut NamedInstances<F>,
 ) -> impl FnOnce(&mut Context<F>, &mut Context<F>, &EthChip<F>) + Clone {
     // the output is a callback function, just take this trait for granted
 
@@ -71,6 +78,6 @@ fn main() {
     env_logger::init();
 
     let args = Cli::parse();
-    // use run_eth instead of run
-    run_eth(compute_fixed_len_keccak, args);
+    // use run_eth_with_dummy instead of run_with_dummy
+    run_eth_with_dummy(compute_fixed_len_keccak, args);
 }
@@ -1,14 +1,13 @@
 use clap::Parser;
 use halo2_base::gates::{GateChip, GateInstructions};
 use halo2_base::utils::ScalarField;
-use halo2_base::AssignedValue;
 #[allow(unused_imports)]
 use halo2_base::{
     Context,
     QuantumCell::{Constant, Existing, Witness},
 };
 use halo2_scaffold::scaffold::cmd::Cli;
-use halo2_scaffold::scaffold::run;
+use halo2_scaffold::scaffold::{run_with_dummy, DummyInput, NamedInstances};
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -16,11 +15,17 @@ pub struct CircuitInput {
     pub x: String, // field element, but easier to deserialize as a string
 }
 
+impl DummyInput for CircuitInput {
+    fn dummy() -> Self {
+        Self { x: "0".to_string() }
+    }
+}
+
 // this algorithm takes a public input x, computes x^2 + 72, and outputs the result as public output
 fn some_algorithm_in_zk<F: ScalarField>(
     ctx: &mut Context<F>,
     input: CircuitInput,
-    make_public: &mut Vec<AssignedValue<F>>,
+    make_public: &mut NamedInstances<F>,
 ) {
     let x = F::from_str_vartime(&input.x).expect("deserialize field element should not fail");
     // `Context` can roughly be thought of as a single-threaded execution trace of a program we want to ZK prove. We do some post-processing on `Context` to optimally divide the execution trace into multiple columns in a PLONKish arithmetization
@@ -29,8 +34,8 @@ fn some_algorithm_in_zk<F: ScalarField>(
     // first we load a number `x` into as system, as a "witness"
     let x = ctx.load_witness(x);
     // by default, all numbers in the system are private
-    // we can make it public like so:
-    make_public.push(x);
+    // we can make it public like so. The name labels it in `data/<name>.instances.json` after proving:
+    make_public.push_named("x", x);
 
     // create a Gate chip that contains methods for basic arithmetic operations
     let gate = GateChip::<F>::default();
@@ -48,7 +53,7 @@ fn some_algorithm_in_zk<F: ScalarField>(
     // Halo2 does not distinguish between public inputs vs outputs because the verifier seems them all at the same time
     // However in traditional terms, `out` is our output number. It is currently still private.
     // Let's make it public:
-    make_public.push(out);
+    make_public.push_named("out", out);
     // ==== way 2 =======
     // here is a more optimal way to compute x^2 + 72 using the lower level `assign_region` API:
     let val = *x.value() * x.value() + c;
@@ -72,5 +77,5 @@ fn main() {
     let args = Cli::parse();
 
     // run different zk commands based on the command line arguments
-    run(some_algorithm_in_zk, args);
+    run_with_dummy(some_algorithm_in_zk, args);
 }
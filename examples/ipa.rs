@@ -0,0 +1,46 @@
+use clap::Parser;
+use halo2_base::gates::{GateChip, GateInstructions};
+use halo2_base::utils::ScalarField;
+use halo2_base::{Context, QuantumCell::Constant};
+use halo2_scaffold::scaffold::cmd::Cli;
+use halo2_scaffold::scaffold::ipa::run_ipa;
+use halo2_scaffold::scaffold::{DummyInput, NamedInstances};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CircuitInput {
+    pub x: String, // field element, but easier to deserialize as a string
+}
+
+impl DummyInput for CircuitInput {
+    fn dummy() -> Self {
+        Self { x: "0".to_string() }
+    }
+}
+
+// the same circuit as in `halo2_lib.rs`: computes x^2 + 72 for a public input x.
+// since it is generic over `F`, it can be proven with either backend; here we use IPA over the Pasta curves,
+// which needs no trusted setup
+fn some_algorithm_in_zk<F: ScalarField>(
+    ctx: &mut Context<F>,
+    input: CircuitInput,
+    make_public: &mut NamedInstances<F>,
+) {
+    let x = F::from_str_vartime(&input.x).expect("deserialize field element should not fail");
+    let x = ctx.load_witness(x);
+    make_public.push_named("x", x);
+
+    let gate = GateChip::<F>::default();
+    let out = gate.mul_add(ctx, x, x, Constant(F::from(72)));
+    make_public.push_named("out", out);
+    println!("out: {:?}", out.value());
+}
+
+fn main() {
+    env_logger::init();
+
+    let args = Cli::parse();
+
+    // `run_ipa` works like `run_with_dummy`, with the IPA commitment scheme instead of KZG
+    run_ipa(some_algorithm_in_zk, args);
+}
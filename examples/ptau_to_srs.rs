@@ -0,0 +1,31 @@
+//! Converts a Powers-of-Tau ceremony file (`.ptau`, e.g. from the Hermez/snarkjs ceremony) into halo2 KZG params.
+//!
+//! The output can be passed to any example with `--srs`:
+//!
+//! ```bash
+//! cargo run --example ptau_to_srs -- --ptau powersOfTau28_hez_final_20.ptau -k 20 --output params/hez_20.srs
+//! ```
+use std::path::PathBuf;
+
+use clap::Parser;
+use halo2_scaffold::scaffold::ptau::convert_ptau;
+
+#[derive(Parser, Debug)]
+struct Args {
+    /// The `.ptau` file to convert
+    #[arg(long = "ptau")]
+    ptau_path: PathBuf,
+    /// Degree of the params to write. Must be at most the power of the ceremony.
+    #[arg(short = 'k', long = "degree")]
+    degree: u32,
+    #[arg(short, long = "output")]
+    output_path: PathBuf,
+}
+
+fn main() {
+    env_logger::init();
+
+    let args = Args::parse();
+    convert_ptau(&args.ptau_path, args.degree, &args.output_path).unwrap_or_else(|e| e.exit());
+    println!("KZG params of degree {} written to: {:?}", args.degree, args.output_path);
+}
@@ -7,9 +7,8 @@ use halo2_base::{
     QuantumCell::{Constant},
 };
 use halo2_scaffold::scaffold::cmd::Cli;
-use halo2_scaffold::scaffold::run;
+use halo2_scaffold::scaffold::{required_lookup_bits, run_with_dummy, DummyInput, NamedInstances};
 use serde::{Deserialize, Serialize};
-use std::env::var;
 use std::vec;
 
 const MAX_PATTERN_LEN: usize = 3;
@@ -24,6 +23,18 @@ pub struct CircuitInput {
     pub input_len: u64,
 }
 
+// the circuit always loads `MAX_PATTERN_LEN` pattern and `MAX_INPUT_LEN` input characters, so the placeholder must have that many
+impl DummyInput for CircuitInput {
+    fn dummy() -> Self {
+        Self {
+            pattern: "0".repeat(MAX_PATTERN_LEN),
+            input_string: "0".repeat(MAX_INPUT_LEN),
+            pattern_len: 0,
+            input_len: 0,
+        }
+    }
+}
+
 // #[derive(Debug, Copy, Clone)]
 // enum Op {
 //     Char = 1,
@@ -100,7 +111,7 @@ fn add_thread<F: ScalarField>(ctx: &mut Context<F>,
 fn regex_parser<F: ScalarField>(
     ctx: &mut Context<F>,
     input: CircuitInput,
-    make_public: &mut Vec<AssignedValue<F>>) {
+    make_public: &mut NamedInstances<F>) {
     let pattern = input.pattern.chars().map(|c| F::from(c as u64)).collect::<Vec<_>>();
     let input_string = input.input_string.chars().map(|c| F::from(c as u64)).collect::<Vec<_>>();
 
@@ -113,9 +124,7 @@ fn regex_parser<F: ScalarField>(
     let input_len = ctx.load_witness(F::from(input.input_len));
 
     let gate = GateChip::<F>::default();
-    let lookup_bits =
-        var("LOOKUP_BITS").unwrap_or_else(|_| panic!("LOOKUP_BITS not set")).parse().unwrap();
-    let range = RangeChip::<F>::default(lookup_bits);
+    let range = RangeChip::<F>::default(required_lookup_bits());
 
 
     let mut code_sequence = Vec::new();
@@ -231,5 +240,5 @@ fn main() {
     let args = Cli::parse();
 
     // run different zk commands based on the command line arguments
-    run(regex_parser, args);
+    run_with_dummy(regex_parser, args);
 }
\ No newline at end of file
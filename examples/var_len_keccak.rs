@@ -1,8 +1,8 @@
 use axiom_eth::{keccak::KeccakChip, EthChip, Field};
 use clap::Parser;
 use ethers_core::utils::keccak256;
-use halo2_base::{gates::RangeInstructions, AssignedValue, Context};
-use halo2_scaffold::scaffold::{cmd::Cli, run_eth};
+use halo2_base::{gates::RangeInstructions, Context};
+use halo2_scaffold::scaffold::{cmd::Cli, run_eth_with_dummy, DummyInput, NamedInstances};
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -11,6 +11,13 @@ pub struct CircuitInput {
     pub len: usize,            // the variable length of the input bytes
 }
 
+// keys generated from this placeholder are for `MAX_LEN = 3`, matching `data/var_len_keccak.in`
+impl DummyInput for CircuitInput {
+    fn dummy() -> Self {
+        Self { padded_bytes: vec![0; 3], len: 0 }
+    }
+}
+
 /// Variable length byte arrays are expressed as a fixed length byte array of length `MAX_LEN`, right padded with arbitrary bytes (typically 0s), together with the actual length of the input bytes.
 /// The bytes (including padding bytes) are range checked to be 8 bits each and exposed as public inputs.
 /// The 32 byte keccak hash of `padded_bytes[..len]` are exposed as public outputs.
@@ -21,7 +28,7 @@ fn compute_var_len_keccak<F: Field>(
     eth_chip: &EthChip<F>,
     keccak: &mut KeccakChip<F>,
     input: CircuitInput,
-    make_public: &mut Vec<AssignedValue<F>>,
+    make_public: &mut NamedInstances<F>,
 ) -> impl FnOnce(&mut Context<F>, &mut Context<F>, &EthChip<F>) + Clone {
     // the output is a callback function, just take this trait for granted
 
@@ -78,6 +85,6 @@ fn main() {
     env_logger::init();
 
     let args = Cli::parse();
-    // use run_eth instead of run
-    run_eth(compute_var_len_keccak, args);
+    // use run_eth_with_dummy instead of run_with_dummy
+    run_eth_with_dummy(compute_var_len_keccak, args);
 }
@@ -0,0 +1,19 @@
+//! Verifies a snark for any circuit created with `run` or `run_builder`, without access to the circuit itself.
+//!
+//! Only the artifacts written by `keygen` and `prove` are needed: `configs/<NAME>.json`, `data/<NAME>.vk` and `data/<NAME>.bundle`.
+//! For example, after proving with the `halo2_lib` example:
+//!
+//! ```bash
+//! cargo run --example verifier -- --name halo2_lib -k auto --unsafe-setup verify
+//! ```
+//!
+//! Pass the same setup as `keygen`: `--unsafe-setup` for the test setup used throughout the README, or `--srs <PATH>`.
+use clap::Parser;
+use halo2_scaffold::scaffold::{cmd::Cli, verifier::run_verifier};
+
+fn main() {
+    env_logger::init();
+
+    let args = Cli::parse();
+    run_verifier(args);
+}
@@ -0,0 +1,119 @@
+//! Aggregation of many snarks created by `prove` into a single snark, e.g. to verify them on-chain for the cost of one.
+//!
+//! The aggregation circuit verifies each snark in-circuit and exposes the resulting KZG accumulator as its first public
+//! instances, followed by the public instances of every aggregated snark in order, so that a verifier of the aggregated
+//! proof also learns what each snark proved. It is an ordinary circuit otherwise, so it goes through the same keygen, prove and verify steps as any other circuit.
+use axiom_eth::util::{
+    circuit::{PinnableCircuit, PreCircuit},
+    AggregationConfigPinning,
+};
+use halo2_base::{
+    gates::builder::CircuitBuilderStage,
+    halo2_proofs::{
+        halo2curves::bn256::{Bn256, Fr},
+        poly::{commitment::Params, kzg::commitment::ParamsKZG},
+    },
+};
+use snark_verifier_sdk::{
+    halo2::{aggregation::AggregationCircuit, read_snark},
+    Snark, SHPLONK,
+};
+
+use super::{
+    batch::glob_inputs,
+    cmd::{Cli, SnarkCmd},
+    error::ScaffoldError,
+    instances::clear_instance_names,
+    pinning::{ScaffoldPinning, AGG_MINIMUM_ROWS},
+    try_run_cli,
+};
+
+#[derive(Clone)]
+pub struct AggregationScaffold {
+    snarks: Vec<Snark>,
+    lookup_bits: Option<usize>,
+}
+
+impl AggregationScaffold {
+    /// Aggregates `snarks`, which must all be SHPLONK snarks with a Poseidon transcript, as created by `prove`.
+    ///
+    /// The lookup table has `2^lookup_bits` rows. `lookup_bits` defaults to `k - 1`, the largest table that fits in
+    /// the circuit, since the in-circuit verifier is dominated by range checks.
+    pub fn new(snarks: Vec<Snark>, lookup_bits: Option<usize>) -> Self {
+        Self { snarks, lookup_bits }
+    }
+}
+
+impl PreCircuit for AggregationScaffold {
+    type Pinning = AggregationConfigPinning;
+
+    fn create_circuit(
+        self,
+        stage: CircuitBuilderStage,
+        pinning: Option<Self::Pinning>,
+        params: &ParamsKZG<Bn256>,
+    ) -> impl PinnableCircuit<Fr> {
+        let k = params.k();
+        let lookup_bits = match &pinning {
+            Some(pinning) => pinning.lookup_bits(),
+            None => self.lookup_bits.unwrap_or(k as usize - 1),
+        };
+        // neither the accumulator nor the re-exposed instances have names
+        clear_instance_names();
+        // break points are only used by the prover; keygen always recomputes them
+        let break_points =
+            pinning.filter(|_| stage == CircuitBuilderStage::Prover).map(|p| p.break_points);
+        let mut circuit = AggregationCircuit::new::<SHPLONK>(
+            stage,
+            break_points,
+            lookup_bits,
+            params,
+            self.snarks,
+        );
+        // the snarks of `prove` are not aggregation snarks, so none of their instances are old accumulators
+        circuit.expose_previous_instances(false);
+        if stage != CircuitBuilderStage::Prover {
+            circuit.config(k, Some(AGG_MINIMUM_ROWS));
+        }
+        circuit
+    }
+}
+
+/// Runs `aggregate <COMMAND>` on the snarks matching `--snarks`, with `--name` naming the aggregation circuit.
+///
+/// Exits the process with [`ScaffoldError::exit_code`] on failure; see [`try_run_aggregation`] for a variant returning the error.
+pub fn run_aggregation(cli: Cli) {
+    try_run_aggregation(cli).unwrap_or_else(|e| e.exit())
+}
+
+pub fn try_run_aggregation(cli: Cli) -> Result<(), ScaffoldError> {
+    let command = match cli.command {
+        SnarkCmd::Aggregate { command } => command,
+        _ => return Err(ScaffoldError::Config("expected the aggregate command".to_string())),
+    };
+    if cli.minimum_rows.is_some() {
+        return Err(ScaffoldError::Config(
+            "--minimum-rows is not supported for aggregate".to_string(),
+        ));
+    }
+    let pattern = cli.snarks.clone().ok_or_else(|| {
+        ScaffoldError::Config(
+            "aggregate needs the snarks to aggregate: pass --snarks <GLOB>".to_string(),
+        )
+    })?;
+    let snarks = glob_inputs(&pattern)?
+        .into_iter()
+        .map(|path| {
+            println!("Aggregating snark: {path:?}");
+            read_snark(&path).map_err(ScaffoldError::parse(&path))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let precircuit = AggregationScaffold::new(snarks, cli.lookup_bits);
+    let mut cli = cli;
+    cli.command = command.into();
+    // the circuit shape only depends on the number of snarks, which the pinning fixes, so checking it is not worth
+    // another synthesis of the whole aggregation circuit
+    cli.no_check_shape = true;
+    try_run_cli(precircuit, cli)
+}
@@ -0,0 +1,257 @@
+//! Proving many inputs against the same proving key.
+//!
+//! The proving key and pinning are loaded once, and one snark is created per input file.
+//! A manifest `data/<name>.manifest.json` records which snark and public instances belong to which input.
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use axiom_eth::util::circuit::PreCircuit;
+use halo2_base::utils::fe_to_biguint;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    aggregatable,
+    cmd::{Cli, SnarkCmd},
+    error::ScaffoldError,
+    instances::write_instances,
+    multiopen::pinned_multiopen,
+    pinning::{read_pinning, ScaffoldPinning},
+    progress::print_progress,
+    read_prover, resolve_degree,
+    srs::load_srs,
+};
+
+/// One proved input in a batch.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BatchManifestEntry {
+    pub input: PathBuf,
+    /// Only written for proofs that can be aggregated
+    pub snark: Option<PathBuf>,
+    /// `None` if the input could not be proved
+    pub bundle: Option<PathBuf>,
+    /// Public instances of the proof, as decimal strings
+    pub instances: Vec<String>,
+    /// Why the input could not be proved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BatchManifest {
+    pub name: String,
+    pub degree: u32,
+    pub proofs: Vec<BatchManifestEntry>,
+}
+
+/// Returns all files matching the glob `pattern`, in sorted order.
+pub fn glob_inputs(pattern: &str) -> Result<Vec<PathBuf>, ScaffoldError> {
+    let paths = glob::glob(pattern)
+        .map_err(|e| ScaffoldError::Config(format!("Invalid input pattern {pattern:?}. {e}")))?;
+    let mut paths = paths
+        .map(|path| {
+            path.map_err(|e| ScaffoldError::Io {
+                path: e.path().to_path_buf(),
+                source: e.into_error(),
+            })
+        })
+        .filter(|path| path.as_ref().map_or(true, |path| path.is_file()))
+        .collect::<Result<Vec<_>, _>>()?;
+    if paths.is_empty() {
+        return Err(ScaffoldError::Config(format!("No input files match {pattern:?}")));
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Creates one snark per path in `input_paths`, using the proving key and pinning created by `keygen`.
+///
+/// `read_precircuit` is called lazily on each input path, so inputs are never all held in memory at once.
+/// Each input is proved by the same [`super::prover::Prover`], which checks it against the circuit shape fingerprinted at keygen.
+/// The proof bundle for `dir/foo.in` is written to `data/foo.bundle`, its labelled public instances to `data/foo.instances.json`
+/// and, if the proof can be aggregated, its snark to `data/foo.snark`.
+///
+/// An input that fails to prove is recorded in the manifest with its error, and the remaining inputs are still proved.
+/// The manifest is rewritten after every input; once all inputs are done, the error of the first failed input is returned.
+pub fn prove_batch<P>(
+    input_paths: Vec<PathBuf>,
+    read_precircuit: impl Fn(&Path) -> Result<P, ScaffoldError>,
+    cli: Cli,
+) -> Result<(), ScaffoldError>
+where
+    P: PreCircuit + Clone,
+    P::Pinning: ScaffoldPinning,
+{
+    if !matches!(cli.command, SnarkCmd::Prove) {
+        return Err(ScaffoldError::Config("--inputs is only supported for prove".to_string()));
+    }
+    if cli.create_contract {
+        return Err(ScaffoldError::Config(
+            "--create-contract is not supported with --inputs; prove each input separately"
+                .to_string(),
+        ));
+    }
+    let output_names = output_names(&input_paths)?;
+    let name = cli.name.clone();
+
+    let config_path = cli.config_path.clone().unwrap_or_else(|| PathBuf::from("configs"));
+    let data_path = cli.data_path.clone().unwrap_or_else(|| PathBuf::from("data"));
+    fs::create_dir_all(&data_path).map_err(ScaffoldError::io(&data_path))?;
+    let pinning_path = config_path.join(PathBuf::from(format!("{name}.json")));
+    let k = resolve_degree::<P>(&cli, &pinning_path)?;
+
+    let pinning: P::Pinning = read_pinning(&pinning_path)?;
+    pinning.check_matches(cli.lookup_bits, cli.minimum_rows)?;
+    pinning.check_degree(k)?;
+    pinned_multiopen(&pinning_path, cli.multiopen)?;
+    let transcript = cli.transcript.unwrap_or_default();
+
+    let params = load_srs(&cli, k)?;
+    let pk_path = data_path.join(PathBuf::from(format!("{name}.pk")));
+    let manifest_path = data_path.join(PathBuf::from(format!("{name}.manifest.json")));
+    let mut batch = Batch {
+        manifest: BatchManifest { name: name.clone(), degree: k, proofs: vec![] },
+        manifest_path,
+        first_error: None,
+    };
+    let mut inputs = input_paths.into_iter().zip(output_names);
+
+    // the proving key is only read once, for the circuit type of the first input that can be read
+    let (prover, first_input) = loop {
+        let Some((input_path, output_name)) = inputs.next() else {
+            return batch.finish();
+        };
+        match read_precircuit(&input_path) {
+            Ok(precircuit) => {
+                let placeholder = precircuit.clone();
+                let prover = read_prover(&name, params, placeholder, &pinning_path, &pk_path)?
+                    .with_shape_check(!cli.no_check_shape);
+                break (prover, (input_path, output_name, precircuit));
+            }
+            Err(e) => batch.record(input_path, Err(e))?,
+        }
+    };
+
+    let prove_input = |input_path: &Path, output_name: &str, precircuit: P| {
+        let snark_path = data_path.join(PathBuf::from(format!("{output_name}.snark")));
+        let bundle_path = data_path.join(PathBuf::from(format!("{output_name}.bundle")));
+        let instances_path = data_path.join(PathBuf::from(format!("{output_name}.instances.json")));
+        // the bundle is for the circuit `name`, whose verifying key all proofs in the batch share
+        let bundle = prover.prove_and_write_snark(
+            precircuit,
+            transcript,
+            &snark_path,
+            &mut print_progress(),
+        )?;
+        write_instances(&name, &bundle.instances[0], &instances_path)?;
+        bundle.write(&bundle_path)?;
+        println!("Proof bundle for {input_path:?} written to: {bundle_path:?}");
+        Ok::<_, ScaffoldError>(BatchManifestEntry {
+            input: input_path.to_path_buf(),
+            snark: aggregatable(transcript, prover.multiopen()).then_some(snark_path),
+            bundle: Some(bundle_path),
+            instances: bundle.instances[0].iter().map(|x| fe_to_biguint(x).to_string()).collect(),
+            error: None,
+        })
+    };
+
+    let (input_path, output_name, precircuit) = first_input;
+    let result = prove_input(&input_path, &output_name, precircuit);
+    batch.record(input_path, result)?;
+    for (input_path, output_name) in inputs {
+        let result = read_precircuit(&input_path)
+            .and_then(|precircuit| prove_input(&input_path, &output_name, precircuit));
+        batch.record(input_path, result)?;
+    }
+    batch.finish()
+}
+
+/// The name of the files written for each of `input_paths`: the file stem of the input, e.g. `foo` for `dir/foo.in`.
+///
+/// Returns an error if two inputs have the same file stem, e.g. `a/foo.in` and `b/foo.json`, since their proofs would overwrite each other.
+pub fn output_names(input_paths: &[PathBuf]) -> Result<Vec<String>, ScaffoldError> {
+    let mut inputs_by_name: HashMap<String, &Path> = HashMap::new();
+    input_paths
+        .iter()
+        .map(|input_path| {
+            // `glob_inputs` only returns files, so every path has a file name
+            let name = input_path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+            if let Some(other) = inputs_by_name.insert(name.clone(), input_path) {
+                return Err(ScaffoldError::Config(format!(
+                    "Inputs {other:?} and {input_path:?} would both write their proofs to {name}.*; rename one of them"
+                )));
+            }
+            Ok(name)
+        })
+        .collect()
+}
+
+/// The manifest of a batch in progress.
+struct Batch {
+    manifest: BatchManifest,
+    manifest_path: PathBuf,
+    first_error: Option<ScaffoldError>,
+}
+
+impl Batch {
+    /// Adds the outcome of proving the input at `input_path` to the manifest and rewrites it.
+    fn record(
+        &mut self,
+        input_path: PathBuf,
+        result: Result<BatchManifestEntry, ScaffoldError>,
+    ) -> Result<(), ScaffoldError> {
+        let entry = match result {
+            Ok(entry) => entry,
+            Err(e) => {
+                eprintln!("Failed to prove {input_path:?}: {e}");
+                let entry = BatchManifestEntry {
+                    input: input_path,
+                    snark: None,
+                    bundle: None,
+                    instances: vec![],
+                    error: Some(e.to_string()),
+                };
+                self.first_error.get_or_insert(e);
+                entry
+            }
+        };
+        self.manifest.proofs.push(entry);
+        let path = &self.manifest_path;
+        let f = File::create(path).map_err(ScaffoldError::io(path))?;
+        serde_json::to_writer_pretty(f, &self.manifest)
+            .map_err(|e| ScaffoldError::io(path)(e.into()))
+    }
+
+    fn finish(self) -> Result<(), ScaffoldError> {
+        println!("Batch manifest written to: {:?}", self.manifest_path);
+        match self.first_error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::output_names;
+
+    #[test]
+    fn test_output_names() {
+        let inputs = vec![PathBuf::from("data/batch/a.in"), PathBuf::from("data/batch/b.json")];
+        assert_eq!(output_names(&inputs).unwrap(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_output_names_collision() {
+        let inputs = vec![
+            PathBuf::from("data/batch/a.in"),
+            PathBuf::from("data/batch/b.in"),
+            PathBuf::from("data/other/a.json"),
+        ];
+        assert!(output_names(&inputs).is_err());
+    }
+}
@@ -0,0 +1,223 @@
+//! Repeated timing of witness generation, keygen, prove and verify, for comparing circuit configurations
+//! (e.g. `k` or `--lookup-bits`) and tracking performance regressions over time.
+//!
+//! Every run does its own keygen in memory, so nothing under `configs/` and no key file is read or written.
+use std::{
+    fs,
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+use axiom_eth::util::circuit::PreCircuit;
+use halo2_base::{
+    gates::builder::CircuitBuilderStage,
+    halo2_proofs::{
+        halo2curves::bn256::Bn256,
+        poly::{commitment::Params, kzg::commitment::ParamsKZG},
+    },
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    cmd::Cli,
+    error::ScaffoldError,
+    keys::KeyFormat,
+    multiopen::MultiOpenKind,
+    pinning::{check_lookup_bits, ScaffoldPinning},
+    prover::Prover,
+    stats::CircuitStats,
+    transcript::TranscriptKind,
+};
+
+/// Wall time of one stage over all runs, in milliseconds.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TimingSummary {
+    pub min_ms: f64,
+    pub median_ms: f64,
+    pub max_ms: f64,
+}
+
+impl TimingSummary {
+    /// Summarizes the wall times of all runs of one stage. `times` must not be empty.
+    pub fn new(mut times: Vec<Duration>) -> Self {
+        times.sort();
+        let ms = |time: Duration| time.as_secs_f64() * 1000.0;
+        let mid = times.len() / 2;
+        let median_ms = if times.len() % 2 == 0 {
+            (ms(times[mid - 1]) + ms(times[mid])) / 2.0
+        } else {
+            ms(times[mid])
+        };
+        Self { min_ms: ms(times[0]), median_ms, max_ms: ms(times[times.len() - 1]) }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BenchTimings {
+    /// Creating the circuit from the input in the prover stage with the pinning of keygen, as proving does.
+    /// This is witness generation only: no constraints are recorded and no configuration is computed
+    pub witness: TimingSummary,
+    /// Creating the circuit for keygen and generating the proving key
+    pub keygen: TimingSummary,
+    /// Proving, without checking the input against the circuit shape
+    pub prove: TimingSummary,
+    pub verify: TimingSummary,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BenchReport {
+    pub name: String,
+    pub degree: u32,
+    pub lookup_bits: usize,
+    pub minimum_rows: usize,
+    pub transcript: TranscriptKind,
+    pub multiopen: MultiOpenKind,
+    pub runs: usize,
+    pub timings: BenchTimings,
+    /// Peak resident set size of the process in bytes, including loading the params. Only known on Linux
+    pub peak_rss: Option<u64>,
+    pub key_format: KeyFormat,
+    /// Size in bytes of the proving key serialized with `key_format`, without the header of the key file
+    pub pk_size: usize,
+    /// Size in bytes of the verifying key serialized with `key_format`, without the header of the key file
+    pub vk_size: usize,
+    pub proof_size: usize,
+    pub circuit: CircuitStats,
+}
+
+/// Runs keygen, witness generation, prove and verify `cli.runs` times for the circuit of `precircuit`.
+///
+/// The transcript, multiopen scheme and key format are taken from `cli`, with the same defaults as the other commands.
+pub fn bench<P>(
+    name: &str,
+    params: ParamsKZG<Bn256>,
+    precircuit: P,
+    cli: &Cli,
+) -> Result<BenchReport, ScaffoldError>
+where
+    P: PreCircuit + Clone,
+    P::Pinning: ScaffoldPinning,
+{
+    if cli.runs == 0 {
+        return Err(ScaffoldError::Config("--runs must be at least 1".to_string()));
+    }
+    let k = params.k();
+    let transcript = cli.transcript.unwrap_or_default();
+    let multiopen = cli.multiopen.unwrap_or_default();
+    let (mut witness, mut keygen, mut prove, mut verify) = (vec![], vec![], vec![], vec![]);
+    // the shape and sizes are the same for every run, so they are measured on the first one
+    let mut measured = None;
+    let mut proof_size = 0;
+    for run in 1..=cli.runs {
+        eprintln!("Benchmark run {run}/{}", cli.runs);
+        let run_params = params.clone();
+        let start = Instant::now();
+        let circuit = precircuit.clone().create_circuit(CircuitBuilderStage::Keygen, None, &params);
+        check_lookup_bits()?;
+        let prover = Prover::<P>::from_keygen_circuit(name, run_params, &circuit, multiopen)?;
+        keygen.push(start.elapsed());
+        if measured.is_none() {
+            // keygen synthesized the circuit, so its break points are known
+            let stats = CircuitStats::new(name, k, &circuit);
+            let format = cli.key_format.into();
+            let pk_size = serialized_size(|w| prover.pk().write(w, format));
+            let vk_size = serialized_size(|w| prover.pk().get_vk().write(w, format));
+            measured = Some((stats, pk_size, vk_size, read_pinning::<P>(&prover)?));
+        }
+
+        // witness generation needs the break points of keygen, like the prover
+        let pinning = read_pinning::<P>(&prover)?;
+        pinning.set_var();
+        let start = Instant::now();
+        precircuit.clone().create_circuit(CircuitBuilderStage::Prover, Some(pinning), &params);
+        witness.push(start.elapsed());
+
+        let start = Instant::now();
+        let bundle = prover.prove(precircuit.clone(), transcript)?;
+        prove.push(start.elapsed());
+
+        let start = Instant::now();
+        prover.verify(&bundle)?;
+        verify.push(start.elapsed());
+        proof_size = bundle.proof.len();
+    }
+
+    let (circuit, pk_size, vk_size, pinning) = measured.expect("--runs is at least 1");
+    Ok(BenchReport {
+        name: name.to_string(),
+        degree: k,
+        lookup_bits: pinning.lookup_bits(),
+        minimum_rows: pinning.minimum_rows(),
+        transcript,
+        multiopen,
+        runs: cli.runs,
+        timings: BenchTimings {
+            witness: TimingSummary::new(witness),
+            keygen: TimingSummary::new(keygen),
+            prove: TimingSummary::new(prove),
+            verify: TimingSummary::new(verify),
+        },
+        peak_rss: peak_rss(),
+        key_format: cli.key_format,
+        pk_size,
+        vk_size,
+        proof_size,
+        circuit,
+    })
+}
+
+fn read_pinning<P>(prover: &Prover<P>) -> Result<P::Pinning, ScaffoldError>
+where
+    P: PreCircuit + Clone,
+    P::Pinning: ScaffoldPinning,
+{
+    serde_json::from_value(prover.pinning().clone())
+        .map_err(|e| ScaffoldError::Config(format!("Invalid pinning: {e}")))
+}
+
+/// Counts the bytes written by `write`, without keeping them.
+fn serialized_size(write: impl FnOnce(&mut ByteCounter) -> io::Result<()>) -> usize {
+    let mut counter = ByteCounter(0);
+    write(&mut counter).expect("writing to a counter cannot fail");
+    counter.0
+}
+
+struct ByteCounter(usize);
+
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Peak resident set size of this process in bytes, read from `VmHWM` in `/proc/self/status`.
+fn peak_rss() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kb: u64 =
+        line.trim_start_matches("VmHWM:").trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::TimingSummary;
+
+    #[test]
+    fn test_timing_summary() {
+        let ms = |ms: &[u64]| ms.iter().map(|&ms| Duration::from_millis(ms)).collect::<Vec<_>>();
+        let odd = TimingSummary::new(ms(&[30, 10, 20]));
+        assert_eq!((odd.min_ms, odd.median_ms, odd.max_ms), (10.0, 20.0, 30.0));
+        let even = TimingSummary::new(ms(&[40, 10, 30, 20]));
+        assert_eq!((even.min_ms, even.median_ms, even.max_ms), (10.0, 25.0, 40.0));
+        let single = TimingSummary::new(ms(&[7]));
+        assert_eq!((single.min_ms, single.median_ms, single.max_ms), (7.0, 7.0, 7.0));
+    }
+}
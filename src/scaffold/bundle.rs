@@ -0,0 +1,344 @@
+//! Self-describing proof bundles, written by `prove` next to the bare snark.
+//!
+//! A bundle `data/<name>.bundle` is a binary file containing a versioned JSON header, the public instances and the proof bytes.
+//! The header records everything needed to check that the bundle belongs to a verifying key before running the verifier.
+//! A human-readable sidecar `data/<name>.bundle.json` repeats the header and the instances as decimal strings;
+//! it is informational only, and `verify` always reads the binary bundle.
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use halo2_base::{
+    halo2_proofs::{
+        halo2curves::{
+            bn256::{Fr, G1Affine},
+            group::ff::PrimeField,
+        },
+        helpers::{SerdeCurveAffine, SerdePrimeField},
+        plonk::VerifyingKey,
+        SerdeFormat,
+    },
+    utils::fe_to_biguint,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{error::ScaffoldError, multiopen::MultiOpenKind, transcript::TranscriptKind};
+
+const BUNDLE_MAGIC: &[u8; 4] = b"h2sb";
+/// Version of the bundle format written by this crate. Version 2 added the multiopen scheme to the header.
+pub const BUNDLE_VERSION: u32 = 2;
+/// Oldest bundle format version that can still be read. Version 1 bundles have no multiopen scheme and are SHPLONK.
+pub const MIN_BUNDLE_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleHeader {
+    pub version: u32,
+    /// Version of `halo2-scaffold` that created the proof
+    pub scaffold_version: String,
+    pub name: String,
+    pub degree: u32,
+    /// Hex SHA-256 digest of the verifying key in `SerdeFormat::RawBytes`, as recorded in the header of the `.vk` file written by `keygen`
+    pub vk_digest: String,
+    /// The transcript the proof was created with; the verifier must use the same one
+    pub transcript: TranscriptKind,
+    /// The multiopen scheme the proof was created with. Version 1 bundles do not record it and are SHPLONK.
+    #[serde(default)]
+    pub multiopen: MultiOpenKind,
+}
+
+/// The contents of the human-readable sidecar
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BundleSidecar {
+    #[serde(flatten)]
+    pub header: BundleHeader,
+    /// Public instances per instance column, as decimal strings
+    pub instances: Vec<Vec<String>>,
+    pub proof_size: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct ProofBundle {
+    pub header: BundleHeader,
+    pub instances: Vec<Vec<Fr>>,
+    pub proof: Vec<u8>,
+}
+
+/// Hex SHA-256 digest of `vk` in `SerdeFormat::RawBytes`. Key files record it in their header, see [`super::keys`].
+pub fn vk_digest<C>(vk: &VerifyingKey<C>) -> String
+where
+    C: SerdeCurveAffine,
+    C::Scalar: SerdePrimeField,
+{
+    let mut bytes = vec![];
+    vk.write(&mut bytes, SerdeFormat::RawBytes).expect("writing vkey to memory should not fail");
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// The path of the sidecar of the bundle at `bundle_path`.
+pub fn sidecar_path(bundle_path: impl AsRef<Path>) -> PathBuf {
+    let mut path = bundle_path.as_ref().as_os_str().to_owned();
+    path.push(".json");
+    PathBuf::from(path)
+}
+
+impl ProofBundle {
+    pub fn new(
+        name: impl Into<String>,
+        degree: u32,
+        vk: &VerifyingKey<G1Affine>,
+        transcript: TranscriptKind,
+        multiopen: MultiOpenKind,
+        instances: Vec<Vec<Fr>>,
+        proof: Vec<u8>,
+    ) -> Self {
+        let header = BundleHeader {
+            version: BUNDLE_VERSION,
+            scaffold_version: env!("CARGO_PKG_VERSION").to_string(),
+            name: name.into(),
+            degree,
+            vk_digest: vk_digest(vk),
+            transcript,
+            multiopen,
+        };
+        Self { header, instances, proof }
+    }
+
+    /// Writes the bundle to `path` and its sidecar to `<path>.json`.
+    pub fn write(&self, path: impl AsRef<Path>) -> Result<(), ScaffoldError> {
+        let path = path.as_ref();
+        let f = File::create(path).map_err(ScaffoldError::io(path))?;
+        self.write_binary(&mut BufWriter::new(f)).map_err(ScaffoldError::io(path))?;
+
+        let sidecar_path = sidecar_path(path);
+        let sidecar = BundleSidecar {
+            header: self.header.clone(),
+            instances: self
+                .instances
+                .iter()
+                .map(|column| column.iter().map(|x| fe_to_biguint(x).to_string()).collect())
+                .collect(),
+            proof_size: self.proof.len(),
+        };
+        let f = File::create(&sidecar_path).map_err(ScaffoldError::io(&sidecar_path))?;
+        serde_json::to_writer_pretty(f, &sidecar)
+            .map_err(|e| ScaffoldError::io(&sidecar_path)(e.into()))
+    }
+
+    /// Reads the binary bundle at `path`. The sidecar is not read.
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, ScaffoldError> {
+        let path = path.as_ref();
+        let f = File::open(path).map_err(ScaffoldError::io(path))?;
+        Self::read_binary(&mut BufReader::new(f))
+            .map_err(|reason| ScaffoldError::Parse { path: path.to_path_buf(), reason })
+    }
+
+    /// The binary bundle, as written by [`Self::write`], e.g. to send it to a verifier.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        self.write_binary(&mut bytes).expect("writing a bundle to memory should not fail");
+        bytes
+    }
+
+    /// Parses a binary bundle created by [`Self::to_bytes`]. Returns the reason if `bytes` is not a valid bundle.
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, String> {
+        Self::read_binary(&mut bytes)
+    }
+
+    /// Checks that this bundle was created for the circuit `name` of degree `degree` with the verifying key with digest `vk_digest`
+    /// and the multiopen scheme `multiopen`, and with `transcript` if one is given.
+    ///
+    /// This only compares the header with the arguments, so it is cheap to run before the verifier.
+    pub fn check(
+        &self,
+        name: &str,
+        degree: u32,
+        vk_digest: &str,
+        transcript: Option<TranscriptKind>,
+        multiopen: MultiOpenKind,
+    ) -> Result<(), ScaffoldError> {
+        let header = &self.header;
+        let mismatch =
+            |what: &str, expected: &dyn std::fmt::Display, found: &dyn std::fmt::Display| {
+                Err(ScaffoldError::BundleMismatch(format!(
+                    "bundle has {what} {found}, but {expected} was expected"
+                )))
+            };
+        if header.name != name {
+            return mismatch("circuit name", &name, &header.name);
+        }
+        if header.degree != degree {
+            return mismatch("degree", &degree, &header.degree);
+        }
+        if header.vk_digest != vk_digest {
+            return mismatch("verifying key digest", &vk_digest, &header.vk_digest);
+        }
+        if let Some(transcript) = transcript.filter(|t| *t != header.transcript) {
+            return mismatch("transcript", &transcript, &header.transcript);
+        }
+        if header.multiopen != multiopen {
+            return mismatch("multiopen scheme", &multiopen, &header.multiopen);
+        }
+        Ok(())
+    }
+
+    fn write_binary(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(BUNDLE_MAGIC)?;
+        writer.write_all(&BUNDLE_VERSION.to_le_bytes())?;
+        write_len_prefixed(writer, &serde_json::to_vec(&self.header)?)?;
+        writer.write_all(&(self.instances.len() as u32).to_le_bytes())?;
+        for column in &self.instances {
+            writer.write_all(&(column.len() as u32).to_le_bytes())?;
+            for x in column {
+                writer.write_all(x.to_repr().as_ref())?;
+            }
+        }
+        write_len_prefixed(writer, &self.proof)?;
+        writer.flush()
+    }
+
+    fn read_binary(reader: &mut impl Read) -> Result<Self, String> {
+        let io_err = |e: io::Error| format!("{e}");
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic).map_err(io_err)?;
+        if &magic != BUNDLE_MAGIC {
+            return Err("not a proof bundle".to_string());
+        }
+        let version = read_u32(reader).map_err(io_err)?;
+        if !(MIN_BUNDLE_VERSION..=BUNDLE_VERSION).contains(&version) {
+            return Err(format!(
+                "bundle format version {version} is not supported (expected {MIN_BUNDLE_VERSION} to {BUNDLE_VERSION})"
+            ));
+        }
+        let header: BundleHeader =
+            serde_json::from_slice(&read_len_prefixed(reader).map_err(io_err)?)
+                .map_err(|e| format!("invalid header: {e}"))?;
+        if header.version != version {
+            return Err("header version does not match the bundle version".to_string());
+        }
+        let num_columns = read_u32(reader).map_err(io_err)?;
+        let instances = (0..num_columns)
+            .map(|_| {
+                let len = read_u32(reader).map_err(io_err)?;
+                (0..len)
+                    .map(|_| {
+                        let mut repr = <Fr as PrimeField>::Repr::default();
+                        reader.read_exact(repr.as_mut()).map_err(io_err)?;
+                        Option::from(Fr::from_repr(repr))
+                            .ok_or_else(|| "public instance is not a field element".to_string())
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let proof = read_len_prefixed(reader).map_err(io_err)?;
+        Ok(Self { header, instances, proof })
+    }
+}
+
+fn write_len_prefixed(writer: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    writer.write_all(bytes)
+}
+
+/// Reads a length and that many bytes. The buffer only grows as bytes arrive, so a corrupted length
+/// fails with `UnexpectedEof` instead of allocating up to 4 GiB.
+fn read_len_prefixed(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let len = read_u32(reader)? as u64;
+    let mut bytes = vec![];
+    reader.by_ref().take(len).read_to_end(&mut bytes)?;
+    if (bytes.len() as u64) < len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("expected {len} bytes, found {}", bytes.len()),
+        ));
+    }
+    Ok(bytes)
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use halo2_base::halo2_proofs::halo2curves::bn256::Fr;
+
+    use super::{
+        BundleHeader, MultiOpenKind, ProofBundle, TranscriptKind, BUNDLE_MAGIC, BUNDLE_VERSION,
+    };
+
+    fn bundle() -> ProofBundle {
+        let header = BundleHeader {
+            version: BUNDLE_VERSION,
+            scaffold_version: "0.1.0".to_string(),
+            name: "test".to_string(),
+            degree: 10,
+            vk_digest: "00".repeat(32),
+            transcript: TranscriptKind::Blake2b,
+            multiopen: MultiOpenKind::Gwc,
+        };
+        ProofBundle { header, instances: vec![vec![Fr::from(1), Fr::from(2)]], proof: vec![7; 100] }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bundle = bundle();
+        let read = ProofBundle::from_bytes(&bundle.to_bytes()).unwrap();
+        assert_eq!(read.header, bundle.header);
+        assert_eq!(read.instances, bundle.instances);
+        assert_eq!(read.proof, bundle.proof);
+    }
+
+    #[test]
+    fn test_read_version_1() {
+        let mut bundle = bundle();
+        bundle.header.version = 1;
+        let mut header = serde_json::to_value(&bundle.header).unwrap();
+        header.as_object_mut().unwrap().remove("multiopen");
+        let header = serde_json::to_vec(&header).unwrap();
+
+        let mut bytes = BUNDLE_MAGIC.to_vec();
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend((header.len() as u32).to_le_bytes());
+        bytes.extend(header);
+        // the instances and proof are laid out as in the current version
+        let current = bundle.to_bytes();
+        let header_end = 12 + u32::from_le_bytes(current[8..12].try_into().unwrap()) as usize;
+        bytes.extend(&current[header_end..]);
+
+        let read = ProofBundle::from_bytes(&bytes).unwrap();
+        assert_eq!(read.header.multiopen, MultiOpenKind::Shplonk);
+        assert_eq!(read.proof, bundle.proof);
+    }
+
+    #[test]
+    fn test_bad_magic() {
+        let mut bytes = bundle().to_bytes();
+        bytes[0] ^= 1;
+        assert_eq!(ProofBundle::from_bytes(&bytes).unwrap_err(), "not a proof bundle");
+    }
+
+    #[test]
+    fn test_bad_version() {
+        let mut bytes = bundle().to_bytes();
+        bytes[4..8].copy_from_slice(&(BUNDLE_VERSION + 1).to_le_bytes());
+        let e = ProofBundle::from_bytes(&bytes).unwrap_err();
+        assert!(e.contains("is not supported"), "{e}");
+    }
+
+    #[test]
+    fn test_truncated() {
+        let bytes = bundle().to_bytes();
+        for len in [0, 6, 20, bytes.len() - 1] {
+            assert!(ProofBundle::from_bytes(&bytes[..len]).is_err(), "{len} bytes");
+        }
+        // a length prefix larger than the input is rejected without allocating it
+        let mut bytes = bytes;
+        bytes[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(ProofBundle::from_bytes(&bytes).is_err());
+    }
+}
@@ -1,5 +1,7 @@
 use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use std::{net::SocketAddr, path::PathBuf, str::FromStr};
+
+use super::{keys::KeyFormat, multiopen::MultiOpenKind, transcript::TranscriptKind};
 
 #[derive(Clone, Copy, Debug, Subcommand)]
 pub enum SnarkCmd {
@@ -11,6 +13,43 @@ pub enum SnarkCmd {
     Prove,
     /// Verify a proof
     Verify,
+    /// Verify the EVM calldata against the Solidity verifier in a local in-process EVM
+    EvmVerify,
+    /// Run mock, keygen, prove and verify in one process, keeping params and keys in memory
+    Full,
+    /// Print a JSON report of the circuit shape and estimated cost
+    Stats,
+    /// Only run the circuit function, without building constraints, and print its public instances and number of advice cells
+    Witness,
+    /// Run witness generation, keygen, prove and verify `--runs` times and print a JSON report of timings, memory and sizes
+    Bench,
+    /// Read the proving keys once and answer JSON-RPC prove and verify requests on a local socket
+    Serve,
+    /// Run keygen, prove or verify for a circuit aggregating the snarks matching `--snarks` into one
+    Aggregate {
+        #[command(subcommand)]
+        command: AggregateCmd,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Subcommand)]
+pub enum AggregateCmd {
+    /// Generate new proving & verifying keys for the aggregation circuit
+    Keygen,
+    /// Generate an aggregated proof
+    Prove,
+    /// Verify an aggregated proof
+    Verify,
+}
+
+impl From<AggregateCmd> for SnarkCmd {
+    fn from(cmd: AggregateCmd) -> Self {
+        match cmd {
+            AggregateCmd::Keygen => Self::Keygen,
+            AggregateCmd::Prove => Self::Prove,
+            AggregateCmd::Verify => Self::Verify,
+        }
+    }
 }
 
 impl std::fmt::Display for SnarkCmd {
@@ -20,11 +59,49 @@ impl std::fmt::Display for SnarkCmd {
             Self::Keygen => write!(f, "keygen"),
             Self::Prove => write!(f, "prove"),
             Self::Verify => write!(f, "verify"),
+            Self::EvmVerify => write!(f, "evm-verify"),
+            Self::Full => write!(f, "full"),
+            Self::Stats => write!(f, "stats"),
+            Self::Witness => write!(f, "witness"),
+            Self::Bench => write!(f, "bench"),
+            Self::Serve => write!(f, "serve"),
+            Self::Aggregate { command } => write!(f, "aggregate {}", Self::from(*command)),
+        }
+    }
+}
+
+/// The degree `k` of a circuit, which has `2^k` rows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Degree {
+    Fixed(u32),
+    /// Search for the degree with the lowest estimated proving cost at `mock`/`keygen`/`stats`/`bench`, and read it from the pinning afterwards.
+    Auto,
+}
+
+impl FromStr for Degree {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            _ => s
+                .parse()
+                .map(Self::Fixed)
+                .map_err(|_| format!("degree must be a number or `auto`, got {s:?}")),
+        }
+    }
+}
+
+impl std::fmt::Display for Degree {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Fixed(k) => write!(f, "{k}"),
+            Self::Auto => write!(f, "auto"),
         }
     }
 }
 
-#[derive(Parser, Debug)]
+#[derive(Clone, Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 /// Command-line helper for various steps in ZK proving.
 pub struct Cli {
@@ -32,12 +109,72 @@ pub struct Cli {
     pub command: SnarkCmd,
     #[arg(short, long = "name")]
     pub name: String,
+    /// The circuit has `2^degree` rows. Use `auto` to pick the degree with the lowest estimated proving cost.
     #[arg(short = 'k', long = "degree")]
-    pub degree: u32,
+    pub degree: Degree,
     #[arg(short, long = "input")]
     pub input_path: Option<PathBuf>,
+    /// Glob of input files to prove in one batch, e.g. 'data/batch/*.in'. Only supported for `prove`.
+    #[arg(long = "inputs", conflicts_with = "input_path")]
+    pub inputs: Option<String>,
+    /// Glob of `.snark` files to aggregate, e.g. 'data/*.snark'. Only used by `aggregate`.
+    #[arg(long = "snarks")]
+    pub snarks: Option<String>,
+    /// Number of bits of the lookup table used for range checks. Defaults to 0 (no lookup table), `ETH_LOOKUP_BITS` for `run_eth` circuits, or `k - 1` for `aggregate`.
+    /// The value used at keygen is recorded in the pinning; `prove` and `verify` reject a different value.
+    #[arg(long = "lookup-bits")]
+    pub lookup_bits: Option<usize>,
+    /// Number of rows at the end of the circuit reserved for blinding factors. Defaults to 9, or 109 for `run_eth` circuits.
+    /// The value used at keygen is recorded in the pinning; `prove` and `verify` reject a different value.
+    #[arg(long = "minimum-rows")]
+    pub minimum_rows: Option<usize>,
+    /// On `keygen`, also write a Solidity verifier contract to `data/<name>.sol` and its deployment bytecode to `data/<name>.bytecode`.
+    /// On `prove`, generate the proof with the EVM transcript and write hex calldata to `data/<name>.calldata` instead of a snark.
     #[arg(long = "create-contract")]
     pub create_contract: bool,
+    /// Transcript hash used by `prove` and `full`, recorded in the proof bundle. Defaults to `poseidon`, the only transcript whose snarks can be aggregated.
+    /// On `verify`, the bundle must have been created with this transcript; if omitted, the transcript recorded in the bundle is used.
+    #[arg(long = "transcript", value_enum)]
+    pub transcript: Option<TranscriptKind>,
+    /// Multiopen scheme of the proofs and the Solidity verifier. Chosen at `keygen` (default `shplonk`) and recorded in the pinning;
+    /// `prove` and `verify` use the pinned scheme and reject a different value.
+    #[arg(long = "multiopen", value_enum)]
+    pub multiopen: Option<MultiOpenKind>,
+    /// Serialization of the proving and verifying keys written by `keygen`. Readers use the format recorded in the key file.
+    #[arg(long = "key-format", value_enum, default_value_t)]
+    pub key_format: KeyFormat,
+    /// Universal trusted setup (KZG params) to use, e.g. from a ceremony. Larger setups are downsized to `degree`.
+    #[arg(long = "srs")]
+    pub srs_path: Option<PathBuf>,
+    /// Generate a random trusted setup if `--srs` is not given. Anyone who knows the randomness can forge proofs, so only use this for testing.
+    #[arg(long = "unsafe-setup", conflicts_with = "srs_path")]
+    pub unsafe_setup: bool,
+    /// Unix socket `serve` listens on. Defaults to `data/<name>.sock`.
+    #[arg(long = "socket", conflicts_with = "http")]
+    pub socket: Option<PathBuf>,
+    /// Localhost address `serve` listens on with HTTP instead of a Unix socket, e.g. `127.0.0.1:3000`.
+    #[arg(long = "http")]
+    pub http: Option<SocketAddr>,
+    /// Comma-separated names of the circuits `serve` reads proving keys for, each created by `keygen` with that `--name`. Defaults to `--name`.
+    #[arg(long = "circuits", value_delimiter = ',')]
+    pub circuits: Vec<String>,
+    /// Make `serve` check each input against the circuit shape of keygen before proving it, which synthesizes the circuit once more.
+    /// `prove` checks unless `--no-check-shape` is given.
+    #[arg(long = "check-shape")]
+    pub check_shape: bool,
+    /// Skip the check of `prove` and `--inputs` that each input has the circuit shape of keygen, which synthesizes the circuit once more.
+    /// `aggregate prove` never checks, since the pinning already fixes its shape.
+    #[arg(long = "no-check-shape", conflicts_with = "check_shape")]
+    pub no_check_shape: bool,
+    /// Number of requests `serve` queues before rejecting new ones while it is busy.
+    #[arg(long = "queue-size", default_value_t = 16)]
+    pub queue_size: usize,
+    /// Seconds after which `serve` cancels a request that has not been answered, whether it is queued or being proved.
+    #[arg(long = "request-timeout")]
+    pub request_timeout: Option<u64>,
+    /// Number of times `bench` runs each stage.
+    #[arg(long = "runs", default_value_t = 5)]
+    pub runs: usize,
     #[arg(short, long = "config-path")]
     pub config_path: Option<PathBuf>,
     #[arg(short, long = "data-path")]
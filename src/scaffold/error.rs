@@ -0,0 +1,118 @@
+//! Errors returned by the `try_*` runners.
+//!
+//! The non-`try` runners (`run`, `run_builder`, ...) print the error and exit the process with [`ScaffoldError::exit_code`],
+//! so scripts can tell a missing file from a failed proof. Services embedding the scaffold should call the `try_*` variants instead.
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+use halo2_base::halo2_proofs::plonk;
+
+#[derive(Debug)]
+pub enum ScaffoldError {
+    /// The command line options are invalid or contradict the pinning
+    Config(String),
+    /// A file could not be read or written
+    Io { path: PathBuf, source: io::Error },
+    /// A file exists but its contents could not be parsed
+    Parse { path: PathBuf, reason: String },
+    /// No pinning was found for the circuit; run `keygen` first
+    MissingPinning(PathBuf),
+    /// The mock prover found unsatisfied constraints
+    Unsatisfied(Vec<String>),
+    /// The circuit could not be synthesized
+    Synthesis(plonk::Error),
+    /// The proof was rejected by the verifier
+    Verification(plonk::Error),
+    /// The proof was rejected by the Solidity verifier, or the verifier could not be deployed
+    EvmVerification(String),
+    /// The proof bundle was not created for the given circuit or verifying key
+    BundleMismatch(String),
+    /// The proving or verifying key was generated for a different circuit
+    KeyMismatch(String),
+    /// The input gives a circuit of a different shape than the one the keys were generated for
+    ShapeMismatch(String),
+    /// Proving was stopped through its [`super::progress::CancellationToken`]
+    Cancelled,
+}
+
+impl ScaffoldError {
+    /// Returns a closure wrapping an `io::Error` about `path`, for use with `map_err`.
+    pub fn io(path: impl AsRef<Path>) -> impl FnOnce(io::Error) -> Self {
+        let path = path.as_ref().to_path_buf();
+        move |source| Self::Io { path, source }
+    }
+
+    /// Returns a closure wrapping a parse error about `path`, for use with `map_err`.
+    pub fn parse<E: fmt::Debug>(path: impl AsRef<Path>) -> impl FnOnce(E) -> Self {
+        let path = path.as_ref().to_path_buf();
+        move |e| Self::Parse { path, reason: format!("{e:?}") }
+    }
+
+    /// The process exit code used by the command line runners. Each variant has its own code.
+    ///
+    /// Code 2 is not used, since `clap` exits with it when the command line cannot be parsed.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Io { .. } => 3,
+            Self::Parse { .. } => 4,
+            Self::MissingPinning(_) => 5,
+            Self::Unsatisfied(_) => 6,
+            Self::Synthesis(_) => 7,
+            Self::Verification(_) => 8,
+            Self::EvmVerification(_) => 9,
+            Self::BundleMismatch(_) => 10,
+            Self::KeyMismatch(_) => 11,
+            Self::ShapeMismatch(_) => 12,
+            Self::Cancelled => 13,
+            Self::Config(_) => 14,
+        }
+    }
+
+    /// Prints the error to stderr and exits the process with [`Self::exit_code`].
+    pub fn exit(self) -> ! {
+        eprintln!("Error: {self}");
+        std::process::exit(self.exit_code())
+    }
+}
+
+impl fmt::Display for ScaffoldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Config(msg) => write!(f, "{msg}"),
+            Self::Io { path, source } => write!(f, "Failed to access file {path:?}: {source}"),
+            Self::Parse { path, reason } => write!(f, "Failed to parse file {path:?}: {reason}"),
+            Self::MissingPinning(path) => {
+                write!(f, "Circuit pinning not found at {path:?}. Run `keygen` first")
+            }
+            Self::Unsatisfied(failures) => {
+                write!(f, "Circuit is not satisfied:")?;
+                for failure in failures {
+                    write!(f, "\n  {failure}")?;
+                }
+                Ok(())
+            }
+            Self::Synthesis(e) => write!(f, "Circuit synthesis failed: {e:?}"),
+            Self::Verification(e) => write!(f, "Snark verification failed: {e:?}"),
+            Self::EvmVerification(reason) => write!(f, "EVM verification failed: {reason}"),
+            Self::BundleMismatch(reason) => write!(f, "Proof bundle does not match: {reason}"),
+            Self::KeyMismatch(reason) => write!(f, "Key does not match the circuit: {reason}"),
+            Self::ShapeMismatch(reason) => write!(
+                f,
+                "Input does not match the circuit shape the keys were generated for: {reason}. \
+                 Run `keygen` again with an input of this shape"
+            ),
+            Self::Cancelled => write!(f, "Proving was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for ScaffoldError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
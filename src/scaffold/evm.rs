@@ -0,0 +1,51 @@
+//! Helpers to check the Solidity verifier end to end without a network node.
+//! The verifier is deployed and called inside an in-process EVM (revm).
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use snark_verifier_sdk::snark_verifier::loader::evm::deploy_and_call;
+
+use super::{cmd::Cli, error::ScaffoldError};
+
+/// Runs `evm-verify`: calls the verifier in `data/<name>.bytecode` with `data/<name>.calldata`.
+///
+/// These are the only files read, so this needs no input, pinning or SRS, and runners dispatch to it before loading any.
+pub fn try_run_evm_verify(cli: Cli) -> Result<(), ScaffoldError> {
+    let name = &cli.name;
+    let data_path = cli.data_path.clone().unwrap_or_else(|| PathBuf::from("data"));
+    let bytecode_path = data_path.join(PathBuf::from(format!("{name}.bytecode")));
+    let calldata_path = data_path.join(PathBuf::from(format!("{name}.calldata")));
+    let gas_used =
+        evm_verify(&bytecode_path, &calldata_path).map_err(ScaffoldError::EvmVerification)?;
+    println!("Snark verified successfully in EVM! Gas used: {gas_used}");
+    Ok(())
+}
+
+/// Deploys the verifier bytecode at `bytecode_path` in a fresh in-memory EVM and calls it with the calldata at `calldata_path`.
+///
+/// Both files are hex encoded, as written by `keygen` and `prove` with `--create-contract`.
+/// Returns the gas used by the verification call, or the revert/halt reason if the call did not succeed.
+pub fn evm_verify(
+    bytecode_path: impl AsRef<Path>,
+    calldata_path: impl AsRef<Path>,
+) -> Result<u64, String> {
+    let deployment_code = read_hex(bytecode_path)?;
+    let calldata = read_hex(calldata_path)?;
+    deploy_and_call(deployment_code, calldata)
+}
+
+/// Writes `bytes` hex encoded to `path`.
+pub fn write_hex(bytes: &[u8], path: impl AsRef<Path>) -> std::io::Result<()> {
+    fs::write(path, hex::encode(bytes))
+}
+
+fn read_hex(path: impl AsRef<Path>) -> Result<Vec<u8>, String> {
+    let path = path.as_ref();
+    let encoded =
+        fs::read_to_string(path).map_err(|e| format!("Failed to open file: {path:?}: {e:?}"))?;
+    let encoded = encoded.trim();
+    hex::decode(encoded.strip_prefix("0x").unwrap_or(encoded))
+        .map_err(|e| format!("File {path:?} is not valid hex: {e:?}"))
+}
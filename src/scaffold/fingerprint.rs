@@ -0,0 +1,110 @@
+//! Detection of inputs whose circuit has a different shape than the one the keys were generated for.
+//!
+//! The layout of a `halo2-lib` circuit depends on its input: an input of a different length can need more cells,
+//! different break points or a different number of public instances. Proving such an input with the pinning and
+//! proving key of another shape fails deep inside halo2, or only once the proof is verified.
+//! Keygen therefore records a [`CircuitFingerprint`] in the pinning file, and `prove` can recompute it from its own
+//! input before proving. This synthesizes the circuit once more, so it is opt-in for long-running provers, see
+//! [`super::prover::Prover::with_shape_check`].
+use std::path::Path;
+
+use axiom_eth::util::{circuit::PinnableCircuit, Halo2ConfigPinning};
+use halo2_base::{halo2_proofs::dev::MockProver, utils::ScalarField};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{
+    error::ScaffoldError,
+    keys::circuit_digest,
+    pinning::{read_pinning_field, write_pinning_field},
+};
+
+/// The field of the pinning file holding the fingerprint
+pub(crate) const FINGERPRINT_FIELD: &str = "fingerprint";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CircuitFingerprint {
+    /// See [`circuit_digest`]
+    pub constraint_system: String,
+    /// Hex SHA-256 digest of the break points, as written to the pinning
+    pub break_points: String,
+    /// Number of public instances in each instance column
+    pub num_instance: Vec<usize>,
+}
+
+impl CircuitFingerprint {
+    /// Fingerprints `circuit`, which must have been built in the keygen stage and already synthesized,
+    /// since break points are only computed during synthesis.
+    pub fn new<F: ScalarField, C: PinnableCircuit<F>>(circuit: &C) -> Result<Self, ScaffoldError> {
+        let pinning = C::Pinning::from_var(circuit.break_points());
+        let break_points = serde_json::to_value(pinning)
+            .and_then(|pinning| serde_json::to_vec(&pinning["break_points"]))
+            .map_err(|e| ScaffoldError::Config(format!("Invalid pinning: {e}")))?;
+        Ok(Self {
+            constraint_system: circuit_digest::<F, C>(),
+            break_points: hex::encode(Sha256::digest(break_points)),
+            num_instance: circuit.num_instance(),
+        })
+    }
+
+    /// Synthesizes `circuit`, which must have been built in the keygen stage, to fingerprint it.
+    ///
+    /// This synthesizes the circuit with the mock prover, without checking any constraints. Besides generating the
+    /// witness once more, the mock prover allocates every column over all `2^k` rows, so this takes about as much time
+    /// and memory as `mock`.
+    pub fn synthesize<F: ScalarField, C: PinnableCircuit<F>>(
+        k: u32,
+        circuit: &C,
+    ) -> Result<Self, ScaffoldError> {
+        MockProver::run(k, circuit, circuit.instances()).map_err(ScaffoldError::Synthesis)?;
+        Self::new(circuit)
+    }
+
+    /// Returns an error describing how `self`, the fingerprint of the circuit about to be proved, differs from `pinned`.
+    pub fn check(&self, pinned: &Self) -> Result<(), ScaffoldError> {
+        if self.num_instance != pinned.num_instance {
+            return Err(ScaffoldError::ShapeMismatch(format!(
+                "the circuit has {:?} public instances, but the keys were generated for {:?}",
+                self.num_instance, pinned.num_instance
+            )));
+        }
+        if self.constraint_system != pinned.constraint_system {
+            return Err(ScaffoldError::ShapeMismatch(
+                "the circuit needs different columns or gates than the circuit the keys were generated for"
+                    .to_string(),
+            ));
+        }
+        if self.break_points != pinned.break_points {
+            return Err(ScaffoldError::ShapeMismatch(
+                "the circuit has different break points than the circuit the keys were generated for"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Records the fingerprint of `circuit` in the pinning file at `pinning_path`, after keygen has written it.
+pub fn write_fingerprint<F: ScalarField, C: PinnableCircuit<F>>(
+    pinning_path: impl AsRef<Path>,
+    circuit: &C,
+) -> Result<(), ScaffoldError> {
+    write_pinning_field(pinning_path, FINGERPRINT_FIELD, &CircuitFingerprint::new(circuit)?)
+}
+
+/// Returns an error if `circuit` does not have the shape fingerprinted at keygen in the pinning file at `pinning_path`.
+///
+/// `circuit` must be built in the keygen stage from the input about to be proved, with the pinned lookup bits
+/// and minimum rows. It is synthesized here to compute its break points, see [`CircuitFingerprint::synthesize`].
+/// Pinnings written before fingerprints were recorded are not checked.
+pub fn check_fingerprint<F: ScalarField, C: PinnableCircuit<F>>(
+    pinning_path: impl AsRef<Path>,
+    k: u32,
+    circuit: &C,
+) -> Result<(), ScaffoldError> {
+    let Some(pinned) = read_pinning_field::<CircuitFingerprint>(pinning_path, FINGERPRINT_FIELD)?
+    else {
+        return Ok(());
+    };
+    CircuitFingerprint::synthesize(k, circuit)?.check(&pinned)
+}
@@ -0,0 +1,199 @@
+//! Names for public instances, and the `data/<name>.instances.json` file written by `prove`.
+//!
+//! Circuit functions expose values by pushing them to `make_public`, a [`NamedInstances`]. Pushing with
+//! [`NamedInstances::push_named`] instead of [`NamedInstances::push`] also records a name for the value, so consumers
+//! of the proof can look outputs up by name rather than by index.
+//!
+//! The runners only see circuits through `PreCircuit`, which hides their type, so the names of the circuit last built
+//! on each thread are kept in thread-local storage for [`named_instances`].
+use std::{cell::RefCell, collections::HashSet, fs::File, ops::Deref, path::Path};
+
+use halo2_base::{
+    utils::{fe_to_biguint, ScalarField},
+    AssignedValue,
+};
+use serde::{Deserialize, Serialize};
+
+use super::error::ScaffoldError;
+
+thread_local! {
+    static INSTANCE_NAMES: RefCell<Vec<Option<String>>> = RefCell::new(vec![]);
+}
+
+/// The values a circuit function makes public, each with an optional name.
+#[derive(Clone, Debug)]
+pub struct NamedInstances<F: ScalarField> {
+    values: Vec<AssignedValue<F>>,
+    names: Vec<Option<String>>,
+}
+
+impl<F: ScalarField> NamedInstances<F> {
+    pub fn new() -> Self {
+        Self { values: vec![], names: vec![] }
+    }
+
+    /// Makes `value` public without a name.
+    pub fn push(&mut self, value: AssignedValue<F>) {
+        self.values.push(value);
+        self.names.push(None);
+    }
+
+    /// Makes `value` public under `name`. Names must be unique within a circuit; a duplicate is reported before proving.
+    pub fn push_named(&mut self, name: impl Into<String>, value: AssignedValue<F>) {
+        self.values.push(value);
+        self.names.push(Some(name.into()));
+    }
+
+    /// The name of each value, `None` if it was made public with [`Self::push`].
+    pub fn names(&self) -> &[Option<String>] {
+        &self.names
+    }
+
+    pub fn into_values(self) -> Vec<AssignedValue<F>> {
+        self.values
+    }
+
+    /// Labels the values with their names. Returns an error if a name is used more than once.
+    pub fn named(&self) -> Result<Vec<NamedInstance>, ScaffoldError> {
+        let values = self.values.iter().map(|x| *x.value()).collect::<Vec<_>>();
+        label(&self.names, &values)
+    }
+
+    /// Keeps the names as those of the circuit last built on this thread, for [`named_instances`].
+    pub(crate) fn record_names(&self) {
+        INSTANCE_NAMES.with(|names| *names.borrow_mut() = self.names.clone());
+    }
+}
+
+impl<F: ScalarField> Default for NamedInstances<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Read-only access to the values; use [`NamedInstances::push`] to add one, so the values and names stay aligned.
+impl<F: ScalarField> Deref for NamedInstances<F> {
+    type Target = [AssignedValue<F>];
+
+    fn deref(&self) -> &Self::Target {
+        &self.values
+    }
+}
+
+impl<F: ScalarField> Extend<AssignedValue<F>> for NamedInstances<F> {
+    fn extend<I: IntoIterator<Item = AssignedValue<F>>>(&mut self, values: I) {
+        for value in values {
+            self.push(value);
+        }
+    }
+}
+
+/// Forgets the names recorded on this thread. Called before each run of a circuit function, so that a circuit function
+/// that panics does not leave the names of an earlier circuit behind.
+pub(crate) fn clear_instance_names() {
+    INSTANCE_NAMES.with(|names| names.borrow_mut().clear());
+}
+
+/// Returns an error if a name is used more than once in the circuit last built on this thread.
+pub(crate) fn check_instance_names() -> Result<(), ScaffoldError> {
+    INSTANCE_NAMES.with(|names| check_unique(&names.borrow()))
+}
+
+fn check_unique(names: &[Option<String>]) -> Result<(), ScaffoldError> {
+    let mut seen = HashSet::new();
+    if let Some(name) = names.iter().flatten().find(|name| !seen.insert(name.as_str())) {
+        return Err(ScaffoldError::Config(format!(
+            "public instance name {name:?} is used more than once"
+        )));
+    }
+    Ok(())
+}
+
+/// One public instance, in the order it was pushed to `make_public`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NamedInstance {
+    pub index: usize,
+    /// `None` if the value was made public with `push` rather than `push_named`
+    pub name: Option<String>,
+    pub decimal: String,
+    /// Big-endian, `0x`-prefixed
+    pub hex: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InstancesFile {
+    pub name: String,
+    pub instances: Vec<NamedInstance>,
+}
+
+/// Labels `instances` with the names pushed by the circuit function of the circuit last built on this thread.
+pub fn named_instances<F: ScalarField>(
+    instances: &[F],
+) -> Result<Vec<NamedInstance>, ScaffoldError> {
+    INSTANCE_NAMES.with(|names| label(&names.borrow(), instances))
+}
+
+fn label<F: ScalarField>(
+    names: &[Option<String>],
+    instances: &[F],
+) -> Result<Vec<NamedInstance>, ScaffoldError> {
+    check_unique(names)?;
+    Ok(instances
+        .iter()
+        .enumerate()
+        .map(|(index, x)| {
+            let x = fe_to_biguint(x);
+            NamedInstance {
+                index,
+                name: names.get(index).cloned().flatten(),
+                decimal: x.to_string(),
+                hex: format!("{x:#x}"),
+            }
+        })
+        .collect())
+}
+
+/// Writes the labelled public instances of the circuit `name` to `path`.
+pub fn write_instances<F: ScalarField>(
+    name: &str,
+    instances: &[F],
+    path: impl AsRef<Path>,
+) -> Result<(), ScaffoldError> {
+    let path = path.as_ref();
+    let file = InstancesFile { name: name.to_string(), instances: named_instances(instances)? };
+    let f = File::create(path).map_err(ScaffoldError::io(path))?;
+    serde_json::to_writer_pretty(f, &file).map_err(|e| ScaffoldError::io(path)(e.into()))
+}
+
+#[cfg(test)]
+mod test {
+    use halo2_base::{gates::builder::GateThreadBuilder, halo2_proofs::halo2curves::bn256::Fr};
+
+    use super::NamedInstances;
+
+    #[test]
+    fn test_named_instances() {
+        let mut builder = GateThreadBuilder::<Fr>::mock();
+        let ctx = builder.main(0);
+        let mut make_public = NamedInstances::new();
+        make_public.push_named("x", ctx.load_witness(Fr::from(1)));
+        make_public.push(ctx.load_witness(Fr::from(2)));
+        make_public.push_named("y", ctx.load_witness(Fr::from(3)));
+
+        let named = make_public.named().unwrap();
+        let names = named.iter().map(|x| x.name.as_deref()).collect::<Vec<_>>();
+        assert_eq!(names, vec![Some("x"), None, Some("y")]);
+        assert_eq!(named[2].decimal, "3");
+        assert_eq!(named[2].hex, "0x3");
+    }
+
+    #[test]
+    fn test_named_instances_duplicate() {
+        let mut builder = GateThreadBuilder::<Fr>::mock();
+        let ctx = builder.main(0);
+        let mut make_public = NamedInstances::new();
+        make_public.push_named("x", ctx.load_witness(Fr::from(1)));
+        make_public.push_named("x", ctx.load_witness(Fr::from(2)));
+        assert!(make_public.named().is_err());
+    }
+}
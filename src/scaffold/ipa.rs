@@ -0,0 +1,240 @@
+//! A backend using the IPA commitment scheme over the Pasta curves, for circuits that must not depend on a trusted setup.
+//!
+//! The KZG backend used by [`super::run`] needs a universal trusted setup (see [`super::srs`]). IPA params are instead
+//! derived from a hash, so anyone can recreate them; the price is larger proofs and verification time linear in the circuit size.
+//! Circuits are built by the same [`CircuitScaffold`] as for KZG, over the Pasta field `Fp` instead of BN254's `Fr`,
+//! so any circuit function written generically over `F: ScalarField` can be passed to [`run_ipa`] instead of `run`.
+//!
+//! Only `mock`, `keygen`, `prove` and `verify` are supported: aggregation, proof bundles and the Solidity verifier
+//! all rely on `snark-verifier`, which only supports KZG over BN254. Proofs use the Blake2b transcript.
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use axiom_eth::util::{circuit::PinnableCircuit, Halo2ConfigPinning};
+use halo2_base::{
+    gates::builder::{CircuitBuilderStage, GateThreadBuilder},
+    halo2_proofs::{
+        halo2curves::{
+            group::ff::PrimeField,
+            pasta::{EqAffine, Fp},
+        },
+        plonk::{create_proof, keygen_pk, keygen_vk, verify_proof},
+        poly::{
+            commitment::ParamsProver,
+            ipa::{
+                commitment::{IPACommitmentScheme, ParamsIPA},
+                multiopen::{ProverIPA, VerifierIPA},
+                strategy::SingleStrategy,
+            },
+        },
+        transcript::{
+            Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer,
+        },
+    },
+    Context,
+};
+use rand::rngs::OsRng;
+use serde::de::DeserializeOwned;
+use snark_verifier_sdk::CircuitExt;
+
+use super::{
+    cmd::{Cli, Degree, SnarkCmd},
+    error::ScaffoldError,
+    fingerprint::{check_fingerprint, write_fingerprint},
+    instances::{check_instance_names, write_instances, InstancesFile, NamedInstances},
+    keys::{read_pk, read_vk, write_keys},
+    mock_prove,
+    pinning::{
+        check_lookup_bits, read_pinning, write_pinning_json, ScaffoldConfigPinning, ScaffoldPinning,
+    },
+    read_input_or_dummy,
+    transcript::TranscriptKind,
+    CircuitScaffold, DummyInput, ScaffoldCircuitBuilder,
+};
+
+/// Runs `cli.command` on the circuit `f` with the IPA backend. On failure, prints the error and exits with [`ScaffoldError::exit_code`].
+pub fn run_ipa<T: DeserializeOwned + DummyInput + Clone + 'static>(
+    f: impl FnOnce(&mut Context<Fp>, T, &mut NamedInstances<Fp>) + Clone + 'static,
+    cli: Cli,
+) {
+    try_run_ipa(f, cli).unwrap_or_else(|e| e.exit())
+}
+
+/// Same as [`run_ipa`], but returns the error instead of exiting the process.
+pub fn try_run_ipa<T: DeserializeOwned + DummyInput + Clone + 'static>(
+    f: impl FnOnce(&mut Context<Fp>, T, &mut NamedInstances<Fp>) + Clone + 'static,
+    cli: Cli,
+) -> Result<(), ScaffoldError> {
+    try_run_ipa_builder(move |builder, inp, public| f(builder.main(0), inp, public), cli)
+}
+
+pub fn run_ipa_builder<T: DeserializeOwned + DummyInput + Clone + 'static>(
+    f: impl FnOnce(&mut GateThreadBuilder<Fp>, T, &mut NamedInstances<Fp>) + Clone + 'static,
+    cli: Cli,
+) {
+    try_run_ipa_builder(f, cli).unwrap_or_else(|e| e.exit())
+}
+
+pub fn try_run_ipa_builder<T: DeserializeOwned + DummyInput + Clone + 'static>(
+    f: impl FnOnce(&mut GateThreadBuilder<Fp>, T, &mut NamedInstances<Fp>) + Clone + 'static,
+    cli: Cli,
+) -> Result<(), ScaffoldError> {
+    let k = match cli.degree {
+        Degree::Fixed(k) => k,
+        Degree::Auto => {
+            return Err(ScaffoldError::Config(
+                "--degree auto is not supported by the IPA backend".to_string(),
+            ))
+        }
+    };
+    let unsupported = [
+        (cli.create_contract, "--create-contract"),
+        (cli.inputs.is_some(), "--inputs"),
+        (cli.srs_path.is_some(), "--srs"),
+        (cli.multiopen.is_some(), "--multiopen"),
+        (cli.transcript.map_or(false, |t| t != TranscriptKind::Blake2b), "--transcript"),
+    ];
+    if let Some((_, option)) = unsupported.iter().find(|(used, _)| *used) {
+        return Err(ScaffoldError::Config(format!("{option} is not supported by the IPA backend")));
+    }
+    if let Some(lookup_bits) = cli.lookup_bits.filter(|&bits| bits != 0 && bits >= k as usize) {
+        return Err(ScaffoldError::Config(format!(
+            "--lookup-bits {lookup_bits} needs to be less than DEGREE {k}"
+        )));
+    }
+    let private_inputs = read_input_or_dummy(&cli, Some(T::dummy))?;
+    let precircuit =
+        CircuitScaffold::new(f, private_inputs, cli.lookup_bits, cli.minimum_rows).with_dummy();
+
+    let name = &cli.name;
+    let config_path = cli.config_path.clone().unwrap_or_else(|| PathBuf::from("configs"));
+    let data_path = cli.data_path.clone().unwrap_or_else(|| PathBuf::from("data"));
+    fs::create_dir_all(&config_path).map_err(ScaffoldError::io(&config_path))?;
+    fs::create_dir_all(&data_path).map_err(ScaffoldError::io(&data_path))?;
+    let pinning_path = config_path.join(PathBuf::from(format!("{name}.json")));
+    let pk_path = data_path.join(PathBuf::from(format!("{name}.pk")));
+    let vk_path = data_path.join(PathBuf::from(format!("{name}.vk")));
+    let proof_path = data_path.join(PathBuf::from(format!("{name}.proof")));
+    let instances_path = data_path.join(PathBuf::from(format!("{name}.instances.json")));
+
+    // the params are transparent: they only depend on `k`
+    let params = ParamsIPA::<EqAffine>::new(k);
+    match cli.command {
+        SnarkCmd::Mock => {
+            let circuit =
+                precircuit.build_circuit::<Fp>(CircuitBuilderStage::Mock, None, k as usize)?;
+            check_lookup_bits()?;
+            mock_prove(k, &circuit)?;
+        }
+        SnarkCmd::Keygen => {
+            let circuit =
+                precircuit.build_circuit::<Fp>(CircuitBuilderStage::Keygen, None, k as usize)?;
+            check_lookup_bits()?;
+            let vk = keygen_vk(&params, &circuit).map_err(ScaffoldError::Synthesis)?;
+            let pk = keygen_pk(&params, vk, &circuit).map_err(ScaffoldError::Synthesis)?;
+            let pinning = ScaffoldConfigPinning::from_var(circuit.break_points());
+            let pinning = serde_json::to_value(pinning)
+                .map_err(|e| ScaffoldError::io(&pinning_path)(e.into()))?;
+            write_pinning_json(&pinning_path, &pinning)?;
+            write_fingerprint(&pinning_path, &circuit)?;
+            write_keys::<EqAffine, ScaffoldCircuitBuilder<Fp>>(
+                &pk,
+                name,
+                k,
+                cli.key_format,
+                &pk_path,
+                &vk_path,
+            )?;
+            println!("Proving key written to: {pk_path:?}");
+            println!("Verifying key written to: {vk_path:?}");
+        }
+        SnarkCmd::Prove => {
+            let pinning: ScaffoldConfigPinning = read_pinning(&pinning_path)?;
+            pinning.check_matches(cli.lookup_bits, cli.minimum_rows)?;
+            pinning.check_degree(k)?;
+            if !cli.no_check_shape {
+                let keygen_circuit = precircuit.clone().build_circuit::<Fp>(
+                    CircuitBuilderStage::Keygen,
+                    Some(pinning.clone()),
+                    k as usize,
+                )?;
+                check_fingerprint(&pinning_path, k, &keygen_circuit)?;
+            }
+            pinning.set_var();
+            let circuit = precircuit.build_circuit::<Fp>(
+                CircuitBuilderStage::Prover,
+                Some(pinning),
+                k as usize,
+            )?;
+            check_instance_names()?;
+            let pk = read_pk::<EqAffine, ScaffoldCircuitBuilder<Fp>>(&pk_path, name, k)?;
+
+            let instances = circuit.instances();
+            write_instances(name, &instances[0], &instances_path)?;
+            println!("Public instances written to: {instances_path:?}");
+            let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+            create_proof::<
+                IPACommitmentScheme<EqAffine>,
+                ProverIPA<'_, EqAffine>,
+                Challenge255<EqAffine>,
+                _,
+                Blake2bWrite<Vec<u8>, EqAffine, Challenge255<EqAffine>>,
+                _,
+            >(&params, &pk, &[circuit], &[&[&instances[0]]], OsRng, &mut transcript)
+            .map_err(ScaffoldError::Synthesis)?;
+            fs::write(&proof_path, transcript.finalize())
+                .map_err(ScaffoldError::io(&proof_path))?;
+            println!("Proof written to: {proof_path:?}");
+        }
+        SnarkCmd::Verify => {
+            let pinning: ScaffoldConfigPinning = read_pinning(&pinning_path)?;
+            pinning.check_matches(cli.lookup_bits, cli.minimum_rows)?;
+            pinning.check_degree(k)?;
+            // `ScaffoldCircuitBuilder::configure` reads the configuration set here
+            pinning.set_var();
+            let vk = read_vk::<EqAffine, ScaffoldCircuitBuilder<Fp>>(&vk_path, name, k)?;
+            let instances = read_ipa_instances(&instances_path)?;
+            let proof = fs::read(&proof_path).map_err(ScaffoldError::io(&proof_path))?;
+
+            let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+            verify_proof::<
+                IPACommitmentScheme<EqAffine>,
+                VerifierIPA<'_, EqAffine>,
+                Challenge255<EqAffine>,
+                Blake2bRead<&[u8], EqAffine, Challenge255<EqAffine>>,
+                SingleStrategy<'_, EqAffine>,
+            >(
+                params.verifier_params(),
+                &vk,
+                SingleStrategy::new(&params),
+                &[&[&instances]],
+                &mut transcript,
+            )
+            .map_err(ScaffoldError::Verification)?;
+            println!("Proof verified successfully!");
+        }
+        cmd => {
+            return Err(ScaffoldError::Config(format!("{cmd} is not supported by the IPA backend")))
+        }
+    }
+    Ok(())
+}
+
+/// Reads the public instances written by `prove` to `path` back into field elements.
+fn read_ipa_instances(path: &Path) -> Result<Vec<Fp>, ScaffoldError> {
+    let f = File::open(path).map_err(ScaffoldError::io(path))?;
+    let file: InstancesFile =
+        serde_json::from_reader(BufReader::new(f)).map_err(ScaffoldError::parse(path))?;
+    file.instances
+        .iter()
+        .map(|instance| {
+            Fp::from_str_vartime(&instance.decimal).ok_or_else(|| ScaffoldError::Parse {
+                path: path.to_path_buf(),
+                reason: format!("public instance {} is not a field element", instance.index),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,424 @@
+//! Proving and verifying key files with an integrity header, written by `keygen`.
+//!
+//! A key file starts with a versioned JSON header recording the circuit the key was generated for: its name, degree
+//! and a digest of its constraint system. The serialized key follows, then a SHA-256 checksum of it.
+//! Readers compare the header with the circuit they are about to run before handing anything to halo2, so a key for
+//! another circuit is a clear error instead of a panic deep inside halo2 after minutes of loading. The checksum is
+//! checked before the key is deserialized, so corrupted key bytes never reach halo2, which may panic or allocate
+//! without bound on them.
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use clap::ValueEnum;
+use halo2_base::halo2_proofs::{
+    arithmetic::Field,
+    helpers::{SerdeCurveAffine, SerdePrimeField},
+    plonk::{Circuit, ConstraintSystem, ProvingKey, VerifyingKey},
+    SerdeFormat,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{bundle::vk_digest, error::ScaffoldError};
+
+const KEY_MAGIC: &[u8; 4] = b"h2sk";
+/// Maximum size in bytes of the JSON header. Headers written by `keygen` are a few hundred bytes.
+const MAX_HEADER_BYTES: u64 = 1 << 16;
+/// Version of the key file format written by this crate. Key files with a different version are rejected.
+///
+/// The header's `circuit_digest` hashes the `Debug` output of `cs.pinned()`, see [`circuit_digest`]. That output is
+/// not a stable format: a halo2 upgrade that changes it changes every digest, so bump this version along with such
+/// an upgrade to tell users to run `keygen` again rather than report a key for a different circuit.
+pub const KEY_FILE_VERSION: u32 = 1;
+
+/// How the key itself is serialized, see `halo2_proofs::SerdeFormat`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyFormat {
+    /// Compressed curve points in Montgomery form, with validity checks on read. Smallest, slowest to read.
+    Processed,
+    /// Uncompressed points in Montgomery form, with validity checks on read
+    #[default]
+    RawBytes,
+    /// Uncompressed points in Montgomery form, without validity checks on read. Fastest to read; only use for trusted files.
+    RawBytesUnchecked,
+}
+
+impl From<KeyFormat> for SerdeFormat {
+    fn from(format: KeyFormat) -> Self {
+        match format {
+            KeyFormat::Processed => SerdeFormat::Processed,
+            KeyFormat::RawBytes => SerdeFormat::RawBytes,
+            KeyFormat::RawBytesUnchecked => SerdeFormat::RawBytesUnchecked,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyKind {
+    Proving,
+    Verifying,
+}
+
+/// The circuit a key was generated for. A key can only be used with the circuit it identifies.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyId {
+    pub kind: KeyKind,
+    pub name: String,
+    pub degree: u32,
+    /// See [`circuit_digest`]
+    pub circuit_digest: String,
+}
+
+impl KeyId {
+    /// Identifies a key for the circuit type `C` named `name` with `2^degree` rows. See [`circuit_digest`] for when to call this.
+    pub fn new<F: Field, C: Circuit<F>>(kind: KeyKind, name: &str, degree: u32) -> Self {
+        Self { kind, name: name.to_string(), degree, circuit_digest: circuit_digest::<F, C>() }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KeyHeader {
+    pub version: u32,
+    #[serde(flatten)]
+    pub id: KeyId,
+    pub format: KeyFormat,
+    /// Hex SHA-256 digest of the verifying key in `SerdeFormat::RawBytes`, as recorded in proof bundles
+    pub vk_digest: String,
+}
+
+/// Hex SHA-256 digest of the constraint system of the circuit type `C`, i.e. of its columns, gates, lookups and permutation.
+///
+/// `C::configure` reads the current circuit configuration, so this must be called after the pinning is set.
+pub fn circuit_digest<F: Field, C: Circuit<F>>() -> String {
+    let mut cs = ConstraintSystem::default();
+    C::configure(&mut cs);
+    hex::encode(Sha256::digest(format!("{:?}", cs.pinned())))
+}
+
+/// Writes the key file body, hashing everything written for the trailing checksum.
+pub struct KeyWriter {
+    inner: BufWriter<File>,
+    hasher: Sha256,
+}
+
+impl Write for KeyWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reads the serialized key of a key file, without the header or the checksum.
+pub type KeyReader = io::Take<BufReader<File>>;
+
+/// Writes a key file to `path` with the given header. `write` serializes the key in `header.format`.
+pub fn write_key(
+    path: impl AsRef<Path>,
+    header: &KeyHeader,
+    write: impl FnOnce(&mut KeyWriter, SerdeFormat) -> io::Result<()>,
+) -> Result<(), ScaffoldError> {
+    let path = path.as_ref();
+    let header_bytes = serde_json::to_vec(header).map_err(|e| ScaffoldError::io(path)(e.into()))?;
+    let f = File::create(path).map_err(ScaffoldError::io(path))?;
+    write_key_file(BufWriter::new(f), &header_bytes, header.format.into(), write)
+        .map_err(ScaffoldError::io(path))
+}
+
+fn write_key_file(
+    mut writer: BufWriter<File>,
+    header: &[u8],
+    format: SerdeFormat,
+    write: impl FnOnce(&mut KeyWriter, SerdeFormat) -> io::Result<()>,
+) -> io::Result<()> {
+    writer.write_all(KEY_MAGIC)?;
+    writer.write_all(&KEY_FILE_VERSION.to_le_bytes())?;
+    writer.write_all(&(header.len() as u32).to_le_bytes())?;
+    writer.write_all(header)?;
+    let mut key_writer = KeyWriter { inner: writer, hasher: Sha256::new() };
+    write(&mut key_writer, format)?;
+    let KeyWriter { mut inner, hasher } = key_writer;
+    inner.write_all(&hasher.finalize())?;
+    inner.flush()
+}
+
+/// Reads the header of the key file at `path`, without reading or checking the key.
+pub fn read_key_header(path: impl AsRef<Path>) -> Result<KeyHeader, ScaffoldError> {
+    let path = path.as_ref();
+    let f = File::open(path).map_err(ScaffoldError::io(path))?;
+    let file_len = f.metadata().map_err(ScaffoldError::io(path))?.len();
+    read_header(&mut BufReader::new(f), file_len)
+        .map(|(header, _)| header)
+        .map_err(|reason| ScaffoldError::Parse { path: path.to_path_buf(), reason })
+}
+
+/// Reads the key file at `path`, which must have been generated for the circuit `expected`.
+///
+/// The header is checked first, then the key bytes are streamed through the checksum. Only if it matches does the
+/// reader seek back for `read` to deserialize the key, so the key is read twice but never held in memory as bytes.
+pub fn read_key<T>(
+    path: impl AsRef<Path>,
+    expected: &KeyId,
+    read: impl FnOnce(&mut KeyReader, SerdeFormat) -> io::Result<T>,
+) -> Result<T, ScaffoldError> {
+    let path = path.as_ref();
+    let parse_error = |reason: String| ScaffoldError::Parse { path: path.to_path_buf(), reason };
+    let f = File::open(path).map_err(ScaffoldError::io(path))?;
+    let file_len = f.metadata().map_err(ScaffoldError::io(path))?.len();
+    let mut reader = BufReader::new(f);
+    let (header, header_len) = read_header(&mut reader, file_len).map_err(parse_error)?;
+    check_key_id(&header.id, expected)?;
+
+    let key_len = file_len
+        .checked_sub(header_len + 32)
+        .ok_or_else(|| parse_error("key file is truncated".to_string()))?;
+    let mut hasher = Sha256::new();
+    let hashed = io::copy(&mut reader.by_ref().take(key_len), &mut hasher)
+        .map_err(ScaffoldError::io(path))?;
+    let mut checksum = [0u8; 32];
+    if hashed < key_len || reader.read_exact(&mut checksum).is_err() {
+        return Err(parse_error("key file is truncated".to_string()));
+    }
+    if hasher.finalize().as_slice() != checksum {
+        return Err(parse_error(
+            "checksum does not match: the key file is corrupted or truncated; run `keygen` again"
+                .to_string(),
+        ));
+    }
+
+    reader.seek(SeekFrom::Start(header_len)).map_err(ScaffoldError::io(path))?;
+    let mut key_reader = reader.take(key_len);
+    read(&mut key_reader, header.format.into())
+        .map_err(|e| parse_error(format!("invalid {:?} key: {e}", header.format)))
+}
+
+fn check_key_id(found: &KeyId, expected: &KeyId) -> Result<(), ScaffoldError> {
+    let mismatch = |what: &str, expected: &dyn std::fmt::Debug, found: &dyn std::fmt::Debug| {
+        Err(ScaffoldError::KeyMismatch(format!(
+            "key was generated for {what} {found:?}, but {expected:?} was expected"
+        )))
+    };
+    if found.kind != expected.kind {
+        return mismatch("key kind", &expected.kind, &found.kind);
+    }
+    if found.name != expected.name {
+        return mismatch("circuit name", &expected.name, &found.name);
+    }
+    if found.degree != expected.degree {
+        return mismatch("degree", &expected.degree, &found.degree);
+    }
+    if found.circuit_digest != expected.circuit_digest {
+        return Err(ScaffoldError::KeyMismatch(
+            "key was generated for a circuit of a different shape; run `keygen` again".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Reads the header of a key file of `file_len` bytes and returns it with the offset of the key that follows it.
+fn read_header(reader: &mut impl Read, file_len: u64) -> Result<(KeyHeader, u64), String> {
+    let io_err = |e: io::Error| format!("{e}");
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic).map_err(io_err)?;
+    if &magic != KEY_MAGIC {
+        return Err("not a key file written by `keygen`; run `keygen` again".to_string());
+    }
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes).map_err(io_err)?;
+    let version = u32::from_le_bytes(bytes);
+    if version != KEY_FILE_VERSION {
+        return Err(format!(
+            "key file format version {version} is not supported (expected {KEY_FILE_VERSION})"
+        ));
+    }
+    reader.read_exact(&mut bytes).map_err(io_err)?;
+    let len = u32::from_le_bytes(bytes) as u64;
+    let offset = KEY_MAGIC.len() as u64 + 8 + len;
+    if len > MAX_HEADER_BYTES || offset > file_len {
+        return Err(format!("header length {len} is invalid; the key file is corrupted"));
+    }
+    // the buffer only grows as bytes arrive, like `bundle::read_len_prefixed`
+    let mut header = vec![];
+    reader.by_ref().take(len).read_to_end(&mut header).map_err(io_err)?;
+    if (header.len() as u64) < len {
+        return Err("key file is truncated".to_string());
+    }
+    let header: KeyHeader =
+        serde_json::from_slice(&header).map_err(|e| format!("invalid header: {e}"))?;
+    Ok((header, offset))
+}
+
+/// Writes `pk` to `pk_path` and its verifying key to `vk_path`, for the circuit type `Circ` named `name` with `2^degree` rows.
+pub fn write_keys<C, Circ>(
+    pk: &ProvingKey<C>,
+    name: &str,
+    degree: u32,
+    format: KeyFormat,
+    pk_path: impl AsRef<Path>,
+    vk_path: impl AsRef<Path>,
+) -> Result<(), ScaffoldError>
+where
+    C: SerdeCurveAffine,
+    C::Scalar: SerdePrimeField,
+    Circ: Circuit<C::Scalar>,
+{
+    let header = |kind| KeyHeader {
+        version: KEY_FILE_VERSION,
+        id: KeyId::new::<C::Scalar, Circ>(kind, name, degree),
+        format,
+        vk_digest: vk_digest(pk.get_vk()),
+    };
+    write_key(pk_path, &header(KeyKind::Proving), |writer, format| pk.write(writer, format))?;
+    write_key(vk_path, &header(KeyKind::Verifying), |writer, format| {
+        pk.get_vk().write(writer, format)
+    })
+}
+
+/// Reads the proving key at `pk_path`, which must have been generated for the circuit type `Circ` named `name` with `2^degree` rows.
+pub fn read_pk<C, Circ>(
+    pk_path: impl AsRef<Path>,
+    name: &str,
+    degree: u32,
+) -> Result<ProvingKey<C>, ScaffoldError>
+where
+    C: SerdeCurveAffine,
+    C::Scalar: SerdePrimeField,
+    Circ: Circuit<C::Scalar>,
+{
+    let id = KeyId::new::<C::Scalar, Circ>(KeyKind::Proving, name, degree);
+    read_key(pk_path, &id, |reader, format| ProvingKey::read::<_, Circ>(reader, format))
+}
+
+/// Reads the verifying key at `vk_path`, which must have been generated for the circuit type `Circ` named `name` with `2^degree` rows.
+pub fn read_vk<C, Circ>(
+    vk_path: impl AsRef<Path>,
+    name: &str,
+    degree: u32,
+) -> Result<VerifyingKey<C>, ScaffoldError>
+where
+    C: SerdeCurveAffine,
+    C::Scalar: SerdePrimeField,
+    Circ: Circuit<C::Scalar>,
+{
+    let id = KeyId::new::<C::Scalar, Circ>(KeyKind::Verifying, name, degree);
+    read_key(vk_path, &id, |reader, format| VerifyingKey::read::<_, Circ>(reader, format))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        env, fs,
+        io::{Read, Write},
+        path::PathBuf,
+        process,
+    };
+
+    use super::{read_key, write_key, KeyFormat, KeyHeader, KeyId, KeyKind, KEY_FILE_VERSION};
+    use crate::scaffold::error::ScaffoldError;
+
+    /// Starts like a `VerifyingKey` in `RawBytes`: the degree `k` as one byte, then the first point
+    const KEY: &[u8] = b"\x0anot really a key, but any bytes will do";
+
+    fn id() -> KeyId {
+        KeyId {
+            kind: KeyKind::Verifying,
+            name: "test".to_string(),
+            degree: 10,
+            circuit_digest: "00".repeat(32),
+        }
+    }
+
+    /// Writes a key file with the header of [`id`] to a fresh path under the temporary directory.
+    fn write_test_key(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("halo2-scaffold-{}-{name}.vk", process::id()));
+        let header = KeyHeader {
+            version: KEY_FILE_VERSION,
+            id: id(),
+            format: KeyFormat::RawBytes,
+            vk_digest: "00".repeat(32),
+        };
+        write_key(&path, &header, |writer, _| writer.write_all(KEY)).unwrap();
+        path
+    }
+
+    fn read_test_key(path: &PathBuf, expected: &KeyId) -> Result<Vec<u8>, ScaffoldError> {
+        read_key(path, expected, |reader, _| {
+            let mut key = vec![];
+            reader.read_to_end(&mut key)?;
+            Ok(key)
+        })
+    }
+
+    #[test]
+    fn test_read_key() {
+        let path = write_test_key("read");
+        assert_eq!(read_test_key(&path, &id()).unwrap(), KEY);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_header_mismatch() {
+        let path = write_test_key("mismatch");
+        let other_name = KeyId { name: "other".to_string(), ..id() };
+        let other_kind = KeyId { kind: KeyKind::Proving, ..id() };
+        let other_shape = KeyId { circuit_digest: "11".repeat(32), ..id() };
+        for expected in [other_name, other_kind, other_shape] {
+            let e = read_test_key(&path, &expected).unwrap_err();
+            assert!(matches!(e, ScaffoldError::KeyMismatch(_)), "{e}");
+        }
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_checksum_corruption() {
+        let path = write_test_key("corrupted");
+        let mut bytes = fs::read(&path).unwrap();
+        // flip a bit of the key, which sits right before the 32 byte checksum
+        let key_byte = bytes.len() - 32 - 1;
+        bytes[key_byte] ^= 1;
+        fs::write(&path, &bytes).unwrap();
+        let e = read_test_key(&path, &id()).unwrap_err();
+        assert!(matches!(e, ScaffoldError::Parse { .. }), "{e}");
+        assert!(e.to_string().contains("checksum"), "{e}");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_corrupted_key_is_not_deserialized() {
+        let path = write_test_key("corrupted-k");
+        let mut bytes = fs::read(&path).unwrap();
+        let key_start = bytes.len() - 32 - KEY.len();
+        // a huge `k` and a damaged point, which halo2 would try to allocate for or panic on
+        bytes[key_start] = 0xff;
+        bytes[key_start + 1] ^= 0x80;
+        fs::write(&path, &bytes).unwrap();
+        let e = read_key(&path, &id(), |_, _| -> std::io::Result<()> {
+            panic!("a key failing its checksum must not be deserialized")
+        })
+        .unwrap_err();
+        assert!(e.to_string().contains("checksum"), "{e}");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_header_length() {
+        let path = write_test_key("header-length");
+        let mut bytes = fs::read(&path).unwrap();
+        // the header length follows the magic and the version
+        bytes[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        fs::write(&path, &bytes).unwrap();
+        let e = read_test_key(&path, &id()).unwrap_err();
+        assert!(matches!(e, ScaffoldError::Parse { .. }), "{e}");
+        assert!(e.to_string().contains("header length"), "{e}");
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -1,132 +1,352 @@
 //! This module contains helper functions to handle some common setup to convert the `some_algorithm_in_zk` function in the examples into a Halo2 circuit.
 //! These functions are not quite general enough to place into `halo2-lib` yet, so they are just some internal helpers for this crate only for now.
 //! We recommend not reading this module on first (or second) pass.
-// use ark_std::{end_timer, start_timer};
 use axiom_eth::{
     keccak::FnSynthesize,
     util::{
         circuit::{PinnableCircuit, PreCircuit},
-        AggregationConfigPinning, Halo2ConfigPinning,
+        Halo2ConfigPinning,
     },
 };
 use halo2_base::{
-    gates::builder::{
-        CircuitBuilderStage, GateThreadBuilder, MultiPhaseThreadBreakPoints, RangeCircuitBuilder,
-        RangeWithInstanceCircuitBuilder, RangeWithInstanceConfig,
+    gates::{
+        builder::{
+            CircuitBuilderStage, FlexGateConfigParams, GateThreadBuilder,
+            MultiPhaseThreadBreakPoints, RangeCircuitBuilder, RangeWithInstanceCircuitBuilder,
+            RangeWithInstanceConfig,
+        },
+        range::{RangeConfig, RangeStrategy},
     },
     halo2_proofs::{
         circuit::{Layouter, SimpleFloorPlanner},
         dev::MockProver,
         halo2curves::bn256::{Bn256, Fr, G1Affine},
-        plonk::{verify_proof, Circuit, ConstraintSystem, Error, ProvingKey, VerifyingKey},
-        poly::{
-            commitment::{Params, ParamsProver},
-            kzg::{
-                commitment::{KZGCommitmentScheme, ParamsKZG},
-                multiopen::VerifierSHPLONK,
-                strategy::SingleStrategy,
-            },
-        },
-        SerdeFormat,
+        plonk::{Circuit, ConstraintSystem, Error, ProvingKey, VerifyingKey},
+        poly::{commitment::Params, kzg::commitment::ParamsKZG},
     },
-    utils::{fs::gen_srs, ScalarField},
-    AssignedValue, Context,
+    utils::ScalarField,
+    Context,
 };
 use serde::de::DeserializeOwned;
 use snark_verifier_sdk::{
-    halo2::{gen_snark_shplonk, read_snark, PoseidonTranscript},
-    read_pk, CircuitExt, NativeLoader,
+    evm::{gen_evm_verifier_gwc, gen_evm_verifier_shplonk, write_calldata},
+    CircuitExt,
 };
 use std::{
-    env::{set_var, var},
     fs::{self, File},
-    io::{BufReader, BufWriter},
+    io::BufReader,
+    iter,
     path::{Path, PathBuf},
+    rc::Rc,
+    time::Instant,
 };
 
-use self::cmd::{Cli, SnarkCmd};
+use self::{
+    bundle::ProofBundle,
+    cmd::{Cli, Degree, SnarkCmd},
+    error::ScaffoldError,
+    instances::{clear_instance_names, write_instances, NamedInstances},
+    keys::{read_key_header, KeyFormat},
+    multiopen::{pinned_multiopen, MultiOpenKind},
+    pinning::{
+        read_pinning, read_pinning_json, write_pinning_json, ScaffoldConfigParams,
+        ScaffoldConfigPinning, ScaffoldPinning, DEFAULT_MINIMUM_ROWS,
+    },
+    progress::print_progress,
+    prover::Prover,
+    srs::load_srs,
+    stats::{BuilderStats, CircuitStats},
+    transcript::TranscriptKind,
+    verifier::Verifier,
+    witness::WitnessReport,
+};
 
+pub mod aggregation;
+pub mod batch;
+pub mod bench;
+pub mod bundle;
 pub mod cmd;
+pub mod error;
+pub mod evm;
+pub mod fingerprint;
+pub mod instances;
+pub mod ipa;
+pub mod keys;
+pub mod multiopen;
+pub mod pinning;
+pub mod progress;
+pub mod prover;
+pub mod ptau;
+pub mod server;
+pub mod srs;
+pub mod stats;
+pub mod transcript;
+pub mod verifier;
+pub mod witness;
+
+pub use instances::NamedInstances;
+pub use pinning::{lookup_bits, required_lookup_bits};
 ///! The functions below are generic scaffolding functions to create circuits with 'halo2-lib'
 
+#[derive(Clone)]
 pub struct CircuitScaffold<T, Fn> {
     f: Fn,
     private_inputs: T,
+    lookup_bits: Option<usize>,
+    minimum_rows: Option<usize>,
+    /// Placeholder inputs of the right shape, see [`Self::with_dummy`]
+    dummy: Option<fn() -> T>,
+}
+
+/// Private inputs with a placeholder value, so that `keygen` and `verify` can run without an input file.
+///
+/// The placeholder must create a circuit of the same shape as real inputs do; only the witness values may differ.
+/// For example, if the circuit hashes a fixed number of bytes, the placeholder must have that many bytes.
+/// Runners only use the placeholder when the circuit is run with [`run_with_dummy`] or another `*_with_dummy` runner.
+pub trait DummyInput {
+    fn dummy() -> Self;
+}
+
+impl DummyInput for Fr {
+    fn dummy() -> Self {
+        Fr::zero()
+    }
+}
+
+/// Runs `cli.command` on the circuit `f`. On failure, prints the error and exits with [`ScaffoldError::exit_code`].
+///
+/// Every command except `verify` reads the input file, see [`run_with_dummy`] to run `keygen` without one.
+pub fn run<T: DeserializeOwned + Clone + 'static>(
+    f: impl FnOnce(&mut Context<Fr>, T, &mut NamedInstances<Fr>) + Clone + 'static,
+    cli: Cli,
+) {
+    try_run(f, cli).unwrap_or_else(|e| e.exit())
+}
+
+/// Same as [`run`], but returns the error instead of exiting the process.
+pub fn try_run<T: DeserializeOwned + Clone + 'static>(
+    f: impl FnOnce(&mut Context<Fr>, T, &mut NamedInstances<Fr>) + Clone + 'static,
+    cli: Cli,
+) -> Result<(), ScaffoldError> {
+    try_run_builder(move |builder, inp, public| f(builder.main(0), inp, public), cli)
 }
 
-pub fn run<T: DeserializeOwned>(
-    f: impl FnOnce(&mut Context<Fr>, T, &mut Vec<AssignedValue<Fr>>),
+/// Same as [`run`], but `keygen` uses the placeholder [`DummyInput::dummy`] unless `--input` is given, and so does `serve` to read the proving keys.
+pub fn run_with_dummy<T: DeserializeOwned + DummyInput + Clone + 'static>(
+    f: impl FnOnce(&mut Context<Fr>, T, &mut NamedInstances<Fr>) + Clone + 'static,
     cli: Cli,
 ) {
-    run_builder(|builder, inp, public| f(builder.main(0), inp, public), cli)
+    try_run_with_dummy(f, cli).unwrap_or_else(|e| e.exit())
 }
 
-pub fn run_builder<T: DeserializeOwned>(
-    f: impl FnOnce(&mut GateThreadBuilder<Fr>, T, &mut Vec<AssignedValue<Fr>>),
+pub fn try_run_with_dummy<T: DeserializeOwned + DummyInput + Clone + 'static>(
+    f: impl FnOnce(&mut Context<Fr>, T, &mut NamedInstances<Fr>) + Clone + 'static,
+    cli: Cli,
+) -> Result<(), ScaffoldError> {
+    try_run_builder_with_dummy(move |builder, inp, public| f(builder.main(0), inp, public), cli)
+}
+
+pub fn run_builder<T: DeserializeOwned + Clone + 'static>(
+    f: impl FnOnce(&mut GateThreadBuilder<Fr>, T, &mut NamedInstances<Fr>) + Clone + 'static,
     cli: Cli,
 ) {
+    try_run_builder(f, cli).unwrap_or_else(|e| e.exit())
+}
+
+pub fn try_run_builder<T: DeserializeOwned + Clone + 'static>(
+    f: impl FnOnce(&mut GateThreadBuilder<Fr>, T, &mut NamedInstances<Fr>) + Clone + 'static,
+    cli: Cli,
+) -> Result<(), ScaffoldError> {
+    try_run_builder_with(f, cli, None)
+}
+
+pub fn run_builder_with_dummy<T: DeserializeOwned + DummyInput + Clone + 'static>(
+    f: impl FnOnce(&mut GateThreadBuilder<Fr>, T, &mut NamedInstances<Fr>) + Clone + 'static,
+    cli: Cli,
+) {
+    try_run_builder_with_dummy(f, cli).unwrap_or_else(|e| e.exit())
+}
+
+pub fn try_run_builder_with_dummy<T: DeserializeOwned + DummyInput + Clone + 'static>(
+    f: impl FnOnce(&mut GateThreadBuilder<Fr>, T, &mut NamedInstances<Fr>) + Clone + 'static,
+    cli: Cli,
+) -> Result<(), ScaffoldError> {
+    try_run_builder_with(f, cli, Some(T::dummy))
+}
+
+fn try_run_builder_with<T: DeserializeOwned + Clone + 'static>(
+    f: impl FnOnce(&mut GateThreadBuilder<Fr>, T, &mut NamedInstances<Fr>) + Clone + 'static,
+    cli: Cli,
+    dummy: Option<fn() -> T>,
+) -> Result<(), ScaffoldError> {
+    // verifying does not need the circuit function or any inputs
+    if let SnarkCmd::Verify = cli.command {
+        return verifier::try_run_verifier(cli);
+    }
+    if let SnarkCmd::EvmVerify = cli.command {
+        return evm::try_run_evm_verify(cli);
+    }
+    // the aggregation circuit does not run the circuit function
+    if let SnarkCmd::Aggregate { .. } = cli.command {
+        return aggregation::try_run_aggregation(cli);
+    }
+    let scaffold =
+        |input: T| CircuitScaffold { dummy, ..pre_run_builder_on_inputs(f.clone(), &cli, input) };
+    if let Some(pattern) = cli.inputs.clone() {
+        let input_paths = batch::glob_inputs(&pattern)?;
+        return batch::prove_batch(
+            input_paths,
+            |path| Ok(scaffold(read_input(path)?)),
+            cli.clone(),
+        );
+    }
+    if let SnarkCmd::Serve = cli.command {
+        // the proving keys are read for a placeholder circuit, which can be built from any input
+        let placeholder = match dummy {
+            Some(dummy) => dummy(),
+            None => read_input(input_path(&cli))?,
+        };
+        return server::serve(scaffold, placeholder, cli.clone());
+    }
+    let private_inputs = read_input_or_dummy(&cli, dummy)?;
+    try_run_scaffold(scaffold(private_inputs), cli)
+}
+
+/// Reads the private inputs for `cli.command`.
+///
+/// `keygen` and `verify` only need the shape of the circuit, so unless `--input` is given they use `dummy` if there is one.
+pub fn read_input_or_dummy<T: DeserializeOwned>(
+    cli: &Cli,
+    dummy: Option<fn() -> T>,
+) -> Result<T, ScaffoldError> {
+    match (&cli.command, dummy) {
+        (SnarkCmd::Keygen | SnarkCmd::Verify, Some(dummy)) if cli.input_path.is_none() => {
+            Ok(dummy())
+        }
+        _ => read_input(input_path(cli)),
+    }
+}
+
+/// The input file for `cli`: `data/<input>` if `--input` is given, otherwise `data/<name>.in`.
+pub fn input_path(cli: &Cli) -> PathBuf {
     let name = &cli.name;
-    let input_path = PathBuf::from("data")
-        .join(cli.input_path.clone().unwrap_or_else(|| PathBuf::from(format!("{name}.in"))));
-    let private_inputs: T = serde_json::from_reader(
-        File::open(&input_path)
-            .unwrap_or_else(|e| panic!("Input file not found at {input_path:?}. {e:?}")),
-    )
-    .expect("Input file should be a valid JSON file");
-    run_builder_on_inputs(f, cli, private_inputs)
-}
-
-pub fn run_builder_on_inputs<T: DeserializeOwned>(
-    f: impl FnOnce(&mut GateThreadBuilder<Fr>, T, &mut Vec<AssignedValue<Fr>>),
+    PathBuf::from("data")
+        .join(cli.input_path.clone().unwrap_or_else(|| PathBuf::from(format!("{name}.in"))))
+}
+
+/// Reads the private inputs of a circuit from the JSON file at `input_path`.
+pub fn read_input<T: DeserializeOwned>(input_path: impl AsRef<Path>) -> Result<T, ScaffoldError> {
+    let input_path = input_path.as_ref();
+    let f = File::open(input_path).map_err(ScaffoldError::io(input_path))?;
+    serde_json::from_reader(BufReader::new(f)).map_err(ScaffoldError::parse(input_path))
+}
+
+pub fn run_builder_on_inputs<T: DeserializeOwned + Clone + 'static>(
+    f: impl FnOnce(&mut GateThreadBuilder<Fr>, T, &mut NamedInstances<Fr>) + Clone + 'static,
     cli: Cli,
     private_inputs: T,
 ) {
-    let precircuit = pre_run_builder_on_inputs(f, private_inputs);
-    run_cli(precircuit, cli);
+    try_run_builder_on_inputs(f, cli, private_inputs).unwrap_or_else(|e| e.exit())
 }
 
-pub fn pre_run_builder_on_inputs<T>(
-    f: impl FnOnce(&mut GateThreadBuilder<Fr>, T, &mut Vec<AssignedValue<Fr>>),
+pub fn try_run_builder_on_inputs<T: DeserializeOwned + Clone + 'static>(
+    f: impl FnOnce(&mut GateThreadBuilder<Fr>, T, &mut NamedInstances<Fr>) + Clone + 'static,
+    cli: Cli,
     private_inputs: T,
-) -> CircuitScaffold<T, impl FnOnce(&mut GateThreadBuilder<Fr>, T, &mut Vec<AssignedValue<Fr>>)> {
-    CircuitScaffold { f, private_inputs }
+) -> Result<(), ScaffoldError> {
+    try_run_scaffold(pre_run_builder_on_inputs(f, &cli, private_inputs), cli)
+}
+
+/// Runs `cli.command` on `precircuit`, resolving `--degree auto` with [`CircuitScaffold::search_degree`].
+fn try_run_scaffold<T, Fn>(
+    precircuit: CircuitScaffold<T, Fn>,
+    cli: Cli,
+) -> Result<(), ScaffoldError>
+where
+    T: Clone + 'static,
+    Fn: FnOnce(&mut GateThreadBuilder<Fr>, T, &mut NamedInstances<Fr>) + Clone + 'static,
+{
+    let mut cli = cli;
+    if let SnarkCmd::Witness = cli.command {
+        let report = precircuit.generate_witness()?;
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        return Ok(());
+    }
+    if cli.degree == Degree::Auto
+        && matches!(
+            cli.command,
+            SnarkCmd::Mock | SnarkCmd::Keygen | SnarkCmd::Full | SnarkCmd::Stats | SnarkCmd::Bench
+        )
+    {
+        let k = precircuit.clone().search_degree()?;
+        println!("Automatically selected degree k = {k}");
+        cli.degree = Degree::Fixed(k);
+    }
+    try_run_cli(precircuit, cli)
+}
+
+pub fn pre_run_builder_on_inputs<T: Clone>(
+    f: impl FnOnce(&mut GateThreadBuilder<Fr>, T, &mut NamedInstances<Fr>) + Clone,
+    cli: &Cli,
+    private_inputs: T,
+) -> CircuitScaffold<T, impl FnOnce(&mut GateThreadBuilder<Fr>, T, &mut NamedInstances<Fr>) + Clone>
+{
+    CircuitScaffold {
+        f,
+        private_inputs,
+        lookup_bits: cli.lookup_bits,
+        minimum_rows: cli.minimum_rows,
+        dummy: None,
+    }
 }
 
 pub use eth::*;
 mod eth {
-    use std::{
-        cell::RefCell,
-        env::{set_var, var},
-        fs::File,
-        marker::PhantomData,
-        path::PathBuf,
-    };
+    use std::{cell::RefCell, marker::PhantomData};
 
     use axiom_eth::{
-        keccak::{FixedLenRLCs, KeccakChip, VarLenRLCs},
-        rlp::{builder::RlcThreadBuilder, RlpChip},
-        util::{
-            circuit::{PinnableCircuit, PreCircuit},
-            EthConfigPinning,
+        keccak::{FixedLenRLCs, FnSynthesize, KeccakChip, VarLenRLCs},
+        rlp::{
+            builder::{RlcThreadBreakPoints, RlcThreadBuilder},
+            rlc::RlcChip,
+            RlpChip,
         },
-        EthChip, EthCircuitBuilder, ETH_LOOKUP_BITS,
+        util::circuit::{PinnableCircuit, PreCircuit},
+        EthChip, EthCircuitBuilder, EthConfig, EthConfigParams, ETH_LOOKUP_BITS,
     };
     use halo2_base::{
         gates::builder::{CircuitBuilderStage, GateThreadBuilder},
         halo2_proofs::{
+            circuit::{Layouter, SimpleFloorPlanner},
             halo2curves::bn256::{Bn256, Fr},
+            plonk::{Circuit, ConstraintSystem, Error},
             poly::{commitment::Params, kzg::commitment::ParamsKZG},
         },
         safe_types::RangeChip,
-        AssignedValue, Context,
+        Context,
     };
     use serde::de::DeserializeOwned;
+    use snark_verifier_sdk::CircuitExt;
 
-    use super::{cmd::Cli, run_cli};
+    use super::{
+        aggregation, batch,
+        cmd::{Cli, Degree, SnarkCmd},
+        error::ScaffoldError,
+        evm, input_path,
+        instances::{clear_instance_names, NamedInstances},
+        pinning::{
+            eth_config_params, set_eth_config_params, set_lookup_bits, with_config_env,
+            EthScaffoldPinning, ScaffoldPinning, ETH_DEFAULT_MINIMUM_ROWS,
+        },
+        read_input, read_input_or_dummy, server, try_run_cli, verifier, DummyInput,
+    };
 
+    #[derive(Clone)]
     pub struct EthScaffold<T, FN, F1> {
         f: FN,
         private_inputs: T,
+        lookup_bits: Option<usize>,
+        minimum_rows: Option<usize>,
         _f1: PhantomData<F1>,
     }
 
@@ -137,11 +357,11 @@ mod eth {
             &EthChip<Fr>,
             &mut KeccakChip<Fr>,
             T,
-            &mut Vec<AssignedValue<Fr>>,
+            &mut NamedInstances<Fr>,
         ) -> F1,
         F1: FnOnce(&mut Context<Fr>, &mut Context<Fr>, &EthChip<Fr>) + Clone,
     {
-        type Pinning = EthConfigPinning;
+        type Pinning = EthScaffoldPinning;
 
         fn create_circuit(
             self,
@@ -150,24 +370,34 @@ mod eth {
             params: &ParamsKZG<Bn256>,
         ) -> impl PinnableCircuit<Fr> {
             let mut builder = RlcThreadBuilder::new(stage == CircuitBuilderStage::Prover);
-            let lookup_bits: usize =
-                var("LOOKUP_BITS").unwrap_or_else(|_| ETH_LOOKUP_BITS.to_string()).parse().unwrap();
-            set_var("LOOKUP_BITS", lookup_bits.to_string());
+            // use the pinned values if there is a pinning, otherwise the ones from the command line
+            let (lookup_bits, minimum_rows) = match &pinning {
+                Some(pinning) => (pinning.lookup_bits(), pinning.minimum_rows()),
+                None => (
+                    self.lookup_bits.unwrap_or(ETH_LOOKUP_BITS),
+                    self.minimum_rows.unwrap_or(ETH_DEFAULT_MINIMUM_ROWS),
+                ),
+            };
+            set_lookup_bits(lookup_bits);
             let range = RangeChip::default(lookup_bits);
             let chip = EthChip::new(RlpChip::new(&range, None), None);
             let mut keccak = KeccakChip::default();
 
-            let mut assigned_instances = vec![];
+            let mut make_public = NamedInstances::new();
+            clear_instance_names();
             let f_phase1 = (self.f)(
                 &mut builder.gate_builder,
                 &chip,
                 &mut keccak,
                 self.private_inputs,
-                &mut assigned_instances,
+                &mut make_public,
             );
-            let break_points = pinning.map(|p| p.break_points);
+            make_public.record_names();
+            // break points are only used by the prover; keygen always recomputes them
+            let break_points =
+                pinning.filter(|_| stage == CircuitBuilderStage::Prover).map(|p| p.break_points);
             let circuit = EthCircuitBuilder::new(
-                assigned_instances,
+                make_public.into_values(),
                 builder,
                 RefCell::new(keccak),
                 range,
@@ -184,256 +414,952 @@ mod eth {
                 },
             );
             if stage != CircuitBuilderStage::Prover {
-                circuit.config(params.k() as usize, Some(109));
+                // `config` reads the lookup bits from, and writes its result to, environment variables
+                let params = with_config_env(lookup_bits, || {
+                    circuit.config(params.k() as usize, Some(minimum_rows))
+                });
+                set_eth_config_params(&EthConfigParams {
+                    lookup_bits: Some(lookup_bits),
+                    ..params
+                });
             }
-            circuit
+            EthScaffoldCircuit(circuit)
         }
     }
 
+    /// An `EthCircuitBuilder` that reads its configuration from [`EthScaffoldPinning`] instead of environment variables.
+    pub struct EthScaffoldCircuit<FnPhase1: FnSynthesize<Fr>>(EthCircuitBuilder<Fr, FnPhase1>);
+
+    impl<FnPhase1: FnSynthesize<Fr>> Circuit<Fr> for EthScaffoldCircuit<FnPhase1> {
+        type Config = EthConfig<Fr>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self(self.0.without_witnesses())
+        }
+
+        /// Same as `EthCircuitBuilder::configure`, but reads the configuration set by [`EthScaffoldPinning::set_var`] or keygen on this thread.
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            EthConfig::configure(meta, eth_config_params(), 0)
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            self.0.synthesize(config, layouter)
+        }
+    }
+
+    impl<FnPhase1: FnSynthesize<Fr>> CircuitExt<Fr> for EthScaffoldCircuit<FnPhase1> {
+        fn num_instance(&self) -> Vec<usize> {
+            self.0.num_instance()
+        }
+
+        fn instances(&self) -> Vec<Vec<Fr>> {
+            self.0.instances()
+        }
+    }
+
+    impl<FnPhase1: FnSynthesize<Fr>> PinnableCircuit<Fr> for EthScaffoldCircuit<FnPhase1> {
+        type Pinning = EthScaffoldPinning;
+
+        fn break_points(&self) -> RlcThreadBreakPoints {
+            self.0.break_points()
+        }
+    }
+
+    /// The circuit type the verifying keys of [`EthScaffoldCircuit`]s are read for when verifying without the circuit
+    /// function. Its `configure` only reads the pinning, so the type of the second phase does not matter.
+    pub type EthVerifierCircuit = EthScaffoldCircuit<fn(&mut RlcThreadBuilder<Fr>, &RlcChip<Fr>)>;
+
+    /// Runs `cli.command` on the circuit `f`. Every command but `verify` reads the input file, see
+    /// [`run_eth_with_dummy`] to run `keygen` without one.
     pub fn run_eth<T, FN, F1>(f: FN, cli: Cli)
     where
-        T: DeserializeOwned,
+        T: DeserializeOwned + Clone,
         FN: FnOnce(
-            &mut Context<Fr>,
-            &EthChip<Fr>,
-            &mut KeccakChip<Fr>,
-            T,
-            &mut Vec<AssignedValue<Fr>>,
-        ) -> F1,
+                &mut Context<Fr>,
+                &EthChip<Fr>,
+                &mut KeccakChip<Fr>,
+                T,
+                &mut NamedInstances<Fr>,
+            ) -> F1
+            + Clone,
+        F1: FnOnce(&mut Context<Fr>, &mut Context<Fr>, &EthChip<Fr>) + Clone,
+    {
+        try_run_eth(f, cli).unwrap_or_else(|e| e.exit())
+    }
+
+    pub fn try_run_eth<T, FN, F1>(f: FN, cli: Cli) -> Result<(), ScaffoldError>
+    where
+        T: DeserializeOwned + Clone,
+        FN: FnOnce(
+                &mut Context<Fr>,
+                &EthChip<Fr>,
+                &mut KeccakChip<Fr>,
+                T,
+                &mut NamedInstances<Fr>,
+            ) -> F1
+            + Clone,
         F1: FnOnce(&mut Context<Fr>, &mut Context<Fr>, &EthChip<Fr>) + Clone,
     {
-        run_eth_builder(
+        try_run_eth_builder(
             |builder, chip, keccak, inp, public| f(builder.main(0), chip, keccak, inp, public),
             cli,
         )
     }
 
-    pub fn run_eth_builder<T, FN, F1>(f: FN, cli: Cli)
+    /// Same as [`run_eth`], but `keygen` and `verify` use the placeholder [`DummyInput::dummy`] unless `--input` is given,
+    /// and so does `serve` to read the proving keys.
+    pub fn run_eth_with_dummy<T, FN, F1>(f: FN, cli: Cli)
     where
-        T: DeserializeOwned,
+        T: DeserializeOwned + DummyInput + Clone,
         FN: FnOnce(
-            &mut GateThreadBuilder<Fr>,
-            &EthChip<Fr>,
-            &mut KeccakChip<Fr>,
-            T,
-            &mut Vec<AssignedValue<Fr>>,
-        ) -> F1,
+                &mut Context<Fr>,
+                &EthChip<Fr>,
+                &mut KeccakChip<Fr>,
+                T,
+                &mut NamedInstances<Fr>,
+            ) -> F1
+            + Clone,
         F1: FnOnce(&mut Context<Fr>, &mut Context<Fr>, &EthChip<Fr>) + Clone,
     {
-        let name = &cli.name;
-        let input_path = PathBuf::from("data")
-            .join(cli.input_path.clone().unwrap_or_else(|| PathBuf::from(format!("{name}.in"))));
-        let private_inputs: T = serde_json::from_reader(
-            File::open(&input_path)
-                .unwrap_or_else(|e| panic!("Input file not found at {input_path:?}. {e:?}")),
+        try_run_eth_with_dummy(f, cli).unwrap_or_else(|e| e.exit())
+    }
+
+    pub fn try_run_eth_with_dummy<T, FN, F1>(f: FN, cli: Cli) -> Result<(), ScaffoldError>
+    where
+        T: DeserializeOwned + DummyInput + Clone,
+        FN: FnOnce(
+                &mut Context<Fr>,
+                &EthChip<Fr>,
+                &mut KeccakChip<Fr>,
+                T,
+                &mut NamedInstances<Fr>,
+            ) -> F1
+            + Clone,
+        F1: FnOnce(&mut Context<Fr>, &mut Context<Fr>, &EthChip<Fr>) + Clone,
+    {
+        try_run_eth_builder_with_dummy(
+            |builder, chip, keccak, inp, public| f(builder.main(0), chip, keccak, inp, public),
+            cli,
         )
-        .expect("Input file should be a valid JSON file");
-        run_eth_builder_on_inputs(f, cli, private_inputs)
+    }
+
+    pub fn run_eth_builder<T, FN, F1>(f: FN, cli: Cli)
+    where
+        T: DeserializeOwned + Clone,
+        FN: FnOnce(
+                &mut GateThreadBuilder<Fr>,
+                &EthChip<Fr>,
+                &mut KeccakChip<Fr>,
+                T,
+                &mut NamedInstances<Fr>,
+            ) -> F1
+            + Clone,
+        F1: FnOnce(&mut Context<Fr>, &mut Context<Fr>, &EthChip<Fr>) + Clone,
+    {
+        try_run_eth_builder(f, cli).unwrap_or_else(|e| e.exit())
+    }
+
+    pub fn try_run_eth_builder<T, FN, F1>(f: FN, cli: Cli) -> Result<(), ScaffoldError>
+    where
+        T: DeserializeOwned + Clone,
+        FN: FnOnce(
+                &mut GateThreadBuilder<Fr>,
+                &EthChip<Fr>,
+                &mut KeccakChip<Fr>,
+                T,
+                &mut NamedInstances<Fr>,
+            ) -> F1
+            + Clone,
+        F1: FnOnce(&mut Context<Fr>, &mut Context<Fr>, &EthChip<Fr>) + Clone,
+    {
+        try_run_eth_builder_with(f, cli, None)
+    }
+
+    pub fn run_eth_builder_with_dummy<T, FN, F1>(f: FN, cli: Cli)
+    where
+        T: DeserializeOwned + DummyInput + Clone,
+        FN: FnOnce(
+                &mut GateThreadBuilder<Fr>,
+                &EthChip<Fr>,
+                &mut KeccakChip<Fr>,
+                T,
+                &mut NamedInstances<Fr>,
+            ) -> F1
+            + Clone,
+        F1: FnOnce(&mut Context<Fr>, &mut Context<Fr>, &EthChip<Fr>) + Clone,
+    {
+        try_run_eth_builder_with_dummy(f, cli).unwrap_or_else(|e| e.exit())
+    }
+
+    pub fn try_run_eth_builder_with_dummy<T, FN, F1>(f: FN, cli: Cli) -> Result<(), ScaffoldError>
+    where
+        T: DeserializeOwned + DummyInput + Clone,
+        FN: FnOnce(
+                &mut GateThreadBuilder<Fr>,
+                &EthChip<Fr>,
+                &mut KeccakChip<Fr>,
+                T,
+                &mut NamedInstances<Fr>,
+            ) -> F1
+            + Clone,
+        F1: FnOnce(&mut Context<Fr>, &mut Context<Fr>, &EthChip<Fr>) + Clone,
+    {
+        try_run_eth_builder_with(f, cli, Some(T::dummy))
+    }
+
+    fn try_run_eth_builder_with<T, FN, F1>(
+        f: FN,
+        cli: Cli,
+        dummy: Option<fn() -> T>,
+    ) -> Result<(), ScaffoldError>
+    where
+        T: DeserializeOwned + Clone,
+        FN: FnOnce(
+                &mut GateThreadBuilder<Fr>,
+                &EthChip<Fr>,
+                &mut KeccakChip<Fr>,
+                T,
+                &mut NamedInstances<Fr>,
+            ) -> F1
+            + Clone,
+        F1: FnOnce(&mut Context<Fr>, &mut Context<Fr>, &EthChip<Fr>) + Clone,
+    {
+        // verifying does not need the circuit function or any inputs
+        if let SnarkCmd::Verify = cli.command {
+            return verifier::try_run_verifier_with::<EthVerifierCircuit, EthScaffoldPinning>(cli);
+        }
+        if let SnarkCmd::EvmVerify = cli.command {
+            return evm::try_run_evm_verify(cli);
+        }
+        // the aggregation circuit does not run the circuit function
+        if let SnarkCmd::Aggregate { .. } = cli.command {
+            return aggregation::try_run_aggregation(cli);
+        }
+        // the degree can only be taken from the pinning, since there is no search for eth circuits
+        if cli.degree == Degree::Auto
+            && !matches!(cli.command, SnarkCmd::Prove | SnarkCmd::Verify | SnarkCmd::Serve)
+        {
+            return Err(ScaffoldError::Config(format!(
+                "--degree auto cannot search for a degree for `run_eth` circuits; pass -k <DEGREE> to {}. \
+                 `auto` only works for prove, verify and serve, which read the degree pinned at keygen",
+                cli.command
+            )));
+        }
+        let scaffold = |input: T| EthScaffold {
+            f: f.clone(),
+            private_inputs: input,
+            lookup_bits: cli.lookup_bits,
+            minimum_rows: cli.minimum_rows,
+            _f1: PhantomData,
+        };
+        if let Some(pattern) = cli.inputs.clone() {
+            let input_paths = batch::glob_inputs(&pattern)?;
+            return batch::prove_batch(
+                input_paths,
+                |path| Ok(scaffold(read_input(path)?)),
+                cli.clone(),
+            );
+        }
+        if let SnarkCmd::Serve = cli.command {
+            // the proving keys are read for a placeholder circuit, which can be built from any input
+            let placeholder = match dummy {
+                Some(dummy) => dummy(),
+                None => read_input(input_path(&cli))?,
+            };
+            return server::serve(scaffold, placeholder, cli.clone());
+        }
+        let private_inputs = read_input_or_dummy(&cli, dummy)?;
+        try_run_cli(scaffold(private_inputs), cli)
     }
 
     pub fn run_eth_builder_on_inputs<T, FN, F1>(f: FN, cli: Cli, private_inputs: T)
     where
-        T: DeserializeOwned,
+        T: DeserializeOwned + Clone,
         FN: FnOnce(
-            &mut GateThreadBuilder<Fr>,
-            &EthChip<Fr>,
-            &mut KeccakChip<Fr>,
-            T,
-            &mut Vec<AssignedValue<Fr>>,
-        ) -> F1,
+                &mut GateThreadBuilder<Fr>,
+                &EthChip<Fr>,
+                &mut KeccakChip<Fr>,
+                T,
+                &mut NamedInstances<Fr>,
+            ) -> F1
+            + Clone,
         F1: FnOnce(&mut Context<Fr>, &mut Context<Fr>, &EthChip<Fr>) + Clone,
     {
-        let precircuit = EthScaffold { f, private_inputs, _f1: PhantomData };
-        run_cli(precircuit, cli);
+        try_run_eth_builder_on_inputs(f, cli, private_inputs).unwrap_or_else(|e| e.exit())
+    }
+
+    pub fn try_run_eth_builder_on_inputs<T, FN, F1>(
+        f: FN,
+        cli: Cli,
+        private_inputs: T,
+    ) -> Result<(), ScaffoldError>
+    where
+        T: DeserializeOwned + Clone,
+        FN: FnOnce(
+                &mut GateThreadBuilder<Fr>,
+                &EthChip<Fr>,
+                &mut KeccakChip<Fr>,
+                T,
+                &mut NamedInstances<Fr>,
+            ) -> F1
+            + Clone,
+        F1: FnOnce(&mut Context<Fr>, &mut Context<Fr>, &EthChip<Fr>) + Clone,
+    {
+        let precircuit = EthScaffold {
+            f,
+            private_inputs,
+            lookup_bits: cli.lookup_bits,
+            minimum_rows: cli.minimum_rows,
+            _f1: PhantomData,
+        };
+        try_run_cli(precircuit, cli)
     }
 }
 
-pub fn run_cli<P: PreCircuit>(precircuit: P, cli: Cli) {
-    let name = cli.name;
-    let k = cli.degree;
+pub fn run_cli<P>(precircuit: P, cli: Cli)
+where
+    P: PreCircuit + Clone,
+    P::Pinning: ScaffoldPinning,
+{
+    try_run_cli(precircuit, cli).unwrap_or_else(|e| e.exit())
+}
 
-    let config_path = cli.config_path.unwrap_or_else(|| PathBuf::from("configs"));
-    let data_path = cli.data_path.unwrap_or_else(|| PathBuf::from("data"));
-    fs::create_dir_all(&config_path).unwrap();
-    fs::create_dir_all(&data_path).unwrap();
+pub fn try_run_cli<P>(precircuit: P, cli: Cli) -> Result<(), ScaffoldError>
+where
+    P: PreCircuit + Clone,
+    P::Pinning: ScaffoldPinning,
+{
+    // `aggregate` runs the aggregation circuit instead of `precircuit`
+    if let SnarkCmd::Aggregate { .. } = cli.command {
+        return aggregation::try_run_aggregation(cli);
+    }
+    // `evm-verify` only reads the bytecode and calldata, not the degree, pinning or SRS
+    if let SnarkCmd::EvmVerify = cli.command {
+        return evm::try_run_evm_verify(cli);
+    }
+    if let SnarkCmd::Witness = cli.command {
+        return Err(ScaffoldError::Config(
+            "witness is only supported for circuits run with `run` or `run_builder`".to_string(),
+        ));
+    }
+    if let SnarkCmd::Serve = cli.command {
+        return Err(ScaffoldError::Config(
+            "serve needs to read inputs from requests; it is only supported for circuits run with `run`, `run_builder` or `run_eth`"
+                .to_string(),
+        ));
+    }
+    let name = cli.name.clone();
+
+    let config_path = cli.config_path.clone().unwrap_or_else(|| PathBuf::from("configs"));
+    let data_path = cli.data_path.clone().unwrap_or_else(|| PathBuf::from("data"));
+    fs::create_dir_all(&config_path).map_err(ScaffoldError::io(&config_path))?;
+    fs::create_dir_all(&data_path).map_err(ScaffoldError::io(&data_path))?;
+    let pinning_path = config_path.join(PathBuf::from(format!("{name}.json")));
+    let k = resolve_degree::<P>(&cli, &pinning_path)?;
+    if let Some(lookup_bits) = cli.lookup_bits.filter(|&bits| bits != 0 && bits >= k as usize) {
+        return Err(ScaffoldError::Config(format!(
+            "--lookup-bits {lookup_bits} needs to be less than DEGREE {k}"
+        )));
+    }
 
-    let params = gen_srs(k);
-    println!("Universal trusted setup (unsafe!) available at: params/kzg_bn254_{k}.srs");
+    let params = load_srs(&cli, k)?;
     match cli.command {
         SnarkCmd::Mock => {
             let circuit = precircuit.create_circuit(CircuitBuilderStage::Mock, None, &params);
-            MockProver::run(k, &circuit, circuit.instances()).unwrap().assert_satisfied();
+            pinning::check_lookup_bits()?;
+            mock_prove(k, &circuit)?;
         }
         SnarkCmd::Keygen => {
             let pk_path = data_path.join(PathBuf::from(format!("{name}.pk")));
-            if pk_path.exists() {
-                fs::remove_file(&pk_path).unwrap();
-            }
-            let pinning_path = config_path.join(PathBuf::from(format!("{name}.json")));
-            let pk = precircuit.create_pk(&params, &pk_path, pinning_path);
-            println!("Proving key written to: {pk_path:?}");
-
             let vk_path = data_path.join(PathBuf::from(format!("{name}.vk")));
-            let f = File::create(&vk_path).unwrap();
-            let mut writer = BufWriter::new(f);
-            pk.get_vk()
-                .write(&mut writer, SerdeFormat::RawBytes)
-                .expect("writing vkey should not fail");
+            let circuit = precircuit.create_circuit(CircuitBuilderStage::Keygen, None, &params);
+            pinning::check_lookup_bits()?;
+            let multiopen = cli.multiopen.unwrap_or_default();
+            let prover = Prover::<P>::from_keygen_circuit(&name, params, &circuit, multiopen)?;
+            write_pinning_json(&pinning_path, prover.pinning())?;
+            let pk = prover.pk();
+            custom_write_keys(pk, &circuit, &name, k, cli.key_format, &pk_path, &vk_path)?;
+            println!("Proving key written to: {pk_path:?}");
             println!("Verifying key written to: {vk_path:?}");
+
+            if cli.create_contract {
+                let sol_path = data_path.join(PathBuf::from(format!("{name}.sol")));
+                let (params, vk) = (prover.params(), pk.get_vk());
+                let deployment_code =
+                    custom_gen_evm_verifier(params, vk, &circuit, multiopen, &sol_path);
+                println!("Solidity verifier written to: {sol_path:?}");
+                let bytecode_path = data_path.join(PathBuf::from(format!("{name}.bytecode")));
+                evm::write_hex(&deployment_code, &bytecode_path)
+                    .map_err(ScaffoldError::io(&bytecode_path))?;
+                println!("Verifier deployment bytecode written to: {bytecode_path:?}");
+            }
         }
         SnarkCmd::Prove => {
-            let pinning_path = config_path.join(PathBuf::from(format!("{name}.json")));
-            let pinning = P::Pinning::from_path(pinning_path);
-            pinning.set_var();
-            let circuit =
-                precircuit.create_circuit(CircuitBuilderStage::Prover, Some(pinning), &params);
+            let pinning: P::Pinning = read_pinning(&pinning_path)?;
+            pinning.check_matches(cli.lookup_bits, cli.minimum_rows)?;
+            pinning.check_degree(k)?;
+            pinned_multiopen(&pinning_path, cli.multiopen)?;
+            if let Some(transcript) =
+                cli.transcript.filter(|&t| cli.create_contract && t != TranscriptKind::Keccak)
+            {
+                return Err(ScaffoldError::Config(format!(
+                    "--create-contract proves with the keccak transcript, not {transcript}"
+                )));
+            }
             let pk_path = data_path.join(PathBuf::from(format!("{name}.pk")));
-            let pk = custom_read_pk(pk_path, &circuit);
-            let snark_path = data_path.join(PathBuf::from(format!("{name}.snark")));
-            if snark_path.exists() {
-                fs::remove_file(&snark_path).unwrap();
+            let prover = read_prover(&name, params, precircuit.clone(), &pinning_path, &pk_path)?
+                .with_shape_check(!cli.no_check_shape);
+            let instances_path = data_path.join(PathBuf::from(format!("{name}.instances.json")));
+            if cli.create_contract {
+                // the EVM transcript uses keccak, so this proof can only be checked by the Solidity verifier
+                let bundle = prover.prove_with_progress(
+                    precircuit,
+                    TranscriptKind::Keccak,
+                    &mut print_progress(),
+                )?;
+                write_instances(&name, &bundle.instances[0], &instances_path)?;
+                println!("Public instances written to: {instances_path:?}");
+                let calldata_path = data_path.join(PathBuf::from(format!("{name}.calldata")));
+                write_calldata(&bundle.instances, &bundle.proof, &calldata_path)
+                    .map_err(ScaffoldError::io(&calldata_path))?;
+                println!("EVM calldata written to: {calldata_path:?}");
+            } else {
+                let transcript = cli.transcript.unwrap_or_default();
+                let snark_path = data_path.join(PathBuf::from(format!("{name}.snark")));
+                let bundle = prover.prove_and_write_snark(
+                    precircuit,
+                    transcript,
+                    &snark_path,
+                    &mut print_progress(),
+                )?;
+                write_instances(&name, &bundle.instances[0], &instances_path)?;
+                println!("Public instances written to: {instances_path:?}");
+                if aggregatable(transcript, prover.multiopen()) {
+                    println!("Snark written to: {snark_path:?}");
+                }
+                let bundle_path = data_path.join(PathBuf::from(format!("{name}.bundle")));
+                bundle.write(&bundle_path)?;
+                println!("Proof bundle written to: {bundle_path:?}");
             }
-            gen_snark_shplonk(&params, &pk, circuit, Some(&snark_path));
-            println!("Snark written to: {snark_path:?}");
         }
         SnarkCmd::Verify => {
+            let pinning: P::Pinning = read_pinning(&pinning_path)?;
+            pinning.check_matches(cli.lookup_bits, cli.minimum_rows)?;
+            pinning.check_degree(k)?;
+            let multiopen = pinned_multiopen(&pinning_path, cli.multiopen)?;
             let vk_path = data_path.join(PathBuf::from(format!("{name}.vk")));
-            let circuit = precircuit.create_circuit(CircuitBuilderStage::Keygen, None, &params);
-            let vk = custom_read_vk(vk_path, &circuit);
+            // check that the bundle belongs to this vk before building the circuit or running the verifier
+            let bundle_path = data_path.join(PathBuf::from(format!("{name}.bundle")));
+            let bundle = ProofBundle::read(&bundle_path)?;
+            let vk_digest = read_key_header(&vk_path)?.vk_digest;
+            bundle.check(&name, k, &vk_digest, cli.transcript, multiopen)?;
+            pinning.set_var();
+            // the pinning is only used for the lookup bits and minimum rows; keygen recomputes the break points
+            let circuit =
+                precircuit.create_circuit(CircuitBuilderStage::Keygen, Some(pinning), &params);
+            let vk = custom_read_vk(vk_path, &circuit, &name, k)?;
+            Verifier::new(&name, params, vk, multiopen).verify(&bundle)?;
+            println!("Snark verified successfully!");
+        }
+        SnarkCmd::Stats => {
+            let circuit = precircuit.create_circuit(CircuitBuilderStage::Mock, None, &params);
+            pinning::check_lookup_bits()?;
+            // break points are only computed when the circuit is synthesized
+            mock_prove(k, &circuit)?;
+            let stats = CircuitStats::new(&name, k, &circuit);
+            let stats_path = data_path.join(PathBuf::from(format!("{name}.stats.json")));
+            let f = File::create(&stats_path).map_err(ScaffoldError::io(&stats_path))?;
+            serde_json::to_writer_pretty(f, &stats)
+                .map_err(|e| ScaffoldError::io(&stats_path)(e.into()))?;
+            eprintln!("Circuit stats written to: {stats_path:?}");
+            println!("{}", serde_json::to_string_pretty(&stats).unwrap());
+        }
+        SnarkCmd::Bench => {
+            let report = bench::bench(&name, params, precircuit, &cli)?;
+            let bench_path = data_path.join(PathBuf::from(format!("{name}.bench.json")));
+            let f = File::create(&bench_path).map_err(ScaffoldError::io(&bench_path))?;
+            serde_json::to_writer_pretty(f, &report)
+                .map_err(|e| ScaffoldError::io(&bench_path)(e.into()))?;
+            eprintln!("Benchmark report written to: {bench_path:?}");
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        }
+        SnarkCmd::Full => {
+            // params, pk and vk are kept in memory between stages; only the pinning and snark are written to disk
+            let mut timings = vec![];
+
+            let start = Instant::now();
+            let circuit =
+                precircuit.clone().create_circuit(CircuitBuilderStage::Mock, None, &params);
+            pinning::check_lookup_bits()?;
+            mock_prove(k, &circuit)?;
+            timings.push(("mock", start.elapsed()));
+
+            let start = Instant::now();
+            let circuit =
+                precircuit.clone().create_circuit(CircuitBuilderStage::Keygen, None, &params);
+            pinning::check_lookup_bits()?;
+            let multiopen = cli.multiopen.unwrap_or_default();
+            let prover = Prover::<P>::from_keygen_circuit(&name, params, &circuit, multiopen)?;
+            write_pinning_json(&pinning_path, prover.pinning())?;
+            timings.push(("keygen", start.elapsed()));
+
+            let start = Instant::now();
+            let transcript = cli.transcript.unwrap_or_default();
             let snark_path = data_path.join(PathBuf::from(format!("{name}.snark")));
-            let snark = read_snark(&snark_path)
-                .unwrap_or_else(|e| panic!("Snark not found at {snark_path:?}. {e:?}"));
-
-            let verifier_params = params.verifier_params();
-            let strategy = SingleStrategy::new(&params);
-            let mut transcript =
-                PoseidonTranscript::<NativeLoader, &[u8]>::new::<0>(&snark.proof[..]);
-            let instance = &snark.instances[0][..];
-            verify_proof::<
-                KZGCommitmentScheme<Bn256>,
-                VerifierSHPLONK<'_, Bn256>,
-                _,
-                _,
-                SingleStrategy<'_, Bn256>,
-            >(verifier_params, &vk, strategy, &[&[instance]], &mut transcript)
-            .unwrap();
+            let bundle = prover.prove_and_write_snark(
+                precircuit,
+                transcript,
+                &snark_path,
+                &mut print_progress(),
+            )?;
+            let instances_path = data_path.join(PathBuf::from(format!("{name}.instances.json")));
+            write_instances(&name, &bundle.instances[0], &instances_path)?;
+            let bundle_path = data_path.join(PathBuf::from(format!("{name}.bundle")));
+            bundle.write(&bundle_path)?;
+            timings.push(("prove", start.elapsed()));
+
+            let start = Instant::now();
+            prover.into_verifier().verify(&bundle)?;
+            timings.push(("verify", start.elapsed()));
+
+            if aggregatable(transcript, multiopen) {
+                println!("Snark written to: {snark_path:?}");
+            }
+            println!("Proof bundle written to: {bundle_path:?}");
+            println!("Public instances written to: {instances_path:?}");
+            println!("Summary for {name} (k = {k}):");
+            for (stage, time) in timings {
+                println!("  {stage:<8}{time:?}");
+            }
             println!("Snark verified successfully!");
         }
+        SnarkCmd::Aggregate { .. } => unreachable!("aggregate is dispatched above"),
+        SnarkCmd::EvmVerify => unreachable!("evm-verify is dispatched above"),
+        SnarkCmd::Serve => unreachable!("serve is rejected above"),
+        SnarkCmd::Witness => unreachable!("witness is rejected above"),
     }
+    Ok(())
 }
 
-fn custom_read_pk<C, P>(fname: P, _: &C) -> ProvingKey<G1Affine>
+/// The degree `k` to run `cli.command` with.
+///
+/// With `--degree auto`, `prove` and `verify` use the degree pinned at keygen. For `mock`, `keygen` and `full`,
+/// `run_builder` must have already replaced `auto` by the result of [`CircuitScaffold::search_degree`].
+fn resolve_degree<P: PreCircuit>(cli: &Cli, pinning_path: &Path) -> Result<u32, ScaffoldError> {
+    match cli.degree {
+        Degree::Fixed(k) => Ok(k),
+        Degree::Auto => match cli.command {
+            SnarkCmd::Prove | SnarkCmd::Verify => {
+                Ok(read_pinning::<P::Pinning>(pinning_path)?.degree())
+            }
+            _ => Err(ScaffoldError::Config(
+                "--degree auto is only supported for circuits created with `run` or `run_builder`"
+                    .to_string(),
+            )),
+        },
+    }
+}
+
+/// Creates a [`Prover`] from the pinning and proving key written by `keygen`.
+///
+/// The proving key can only be read for a concrete circuit type, so `precircuit` is built once to identify it.
+/// It is built in the prover stage, which keeps the pinned configuration, so any input works, whatever its shape.
+fn read_prover<P>(
+    name: &str,
+    params: ParamsKZG<Bn256>,
+    precircuit: P,
+    pinning_path: &Path,
+    pk_path: &Path,
+) -> Result<Prover<P>, ScaffoldError>
+where
+    P: PreCircuit + Clone,
+{
+    let pinning: P::Pinning = read_pinning(pinning_path)?;
+    pinning.set_var();
+    let circuit = precircuit.create_circuit(CircuitBuilderStage::Prover, Some(pinning), &params);
+    let pk = custom_read_pk(pk_path, &circuit, name, params.k())?;
+    Prover::new(name, params, pk, read_pinning_json(pinning_path)?)
+}
+
+/// Runs the mock prover on `circuit` and returns the unsatisfied constraints, if any.
+fn mock_prove<F: ScalarField, C: CircuitExt<F>>(k: u32, circuit: &C) -> Result<(), ScaffoldError> {
+    MockProver::run(k, circuit, circuit.instances())
+        .map_err(ScaffoldError::Synthesis)?
+        .verify()
+        .map_err(|failures| {
+            ScaffoldError::Unsatisfied(failures.iter().map(|f| f.to_string()).collect())
+        })
+}
+
+/// Whether proofs with this transcript and multiopen scheme can be aggregated by [`aggregation::AggregationScaffold`].
+fn aggregatable(transcript: TranscriptKind, multiopen: MultiOpenKind) -> bool {
+    transcript == TranscriptKind::Poseidon && multiopen == MultiOpenKind::Shplonk
+}
+
+fn custom_read_pk<C, P>(
+    fname: P,
+    _: &C,
+    name: &str,
+    degree: u32,
+) -> Result<ProvingKey<G1Affine>, ScaffoldError>
 where
     C: Circuit<Fr>,
     P: AsRef<Path>,
 {
-    read_pk::<C>(fname.as_ref())
-        .unwrap_or_else(|e| panic!("Failed to open file: {:?}: {e:?}", fname.as_ref()))
+    keys::read_pk::<G1Affine, C>(fname, name, degree)
 }
 
-fn custom_read_vk<C, P>(fname: P, _: &C) -> VerifyingKey<G1Affine>
+fn custom_write_keys<C, P>(
+    pk: &ProvingKey<G1Affine>,
+    _: &C,
+    name: &str,
+    degree: u32,
+    format: KeyFormat,
+    pk_path: P,
+    vk_path: P,
+) -> Result<(), ScaffoldError>
 where
     C: Circuit<Fr>,
     P: AsRef<Path>,
 {
-    let f = File::open(&fname)
-        .unwrap_or_else(|e| panic!("Failed to open file: {:?}: {e:?}", fname.as_ref()));
-    let mut bufreader = BufReader::new(f);
-    VerifyingKey::read::<_, C>(&mut bufreader, SerdeFormat::RawBytes).expect("Could not read vkey")
+    keys::write_keys::<G1Affine, C>(pk, name, degree, format, pk_path, vk_path)
+}
+
+/// Writes the Solidity verifier for `vk` to `path` and returns the compiled deployment bytecode.
+fn custom_gen_evm_verifier<C>(
+    params: &ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    circuit: &C,
+    multiopen: MultiOpenKind,
+    path: &Path,
+) -> Vec<u8>
+where
+    C: CircuitExt<Fr>,
+{
+    match multiopen {
+        MultiOpenKind::Shplonk => {
+            gen_evm_verifier_shplonk::<C>(params, vk, circuit.num_instance(), Some(path))
+        }
+        MultiOpenKind::Gwc => {
+            gen_evm_verifier_gwc::<C>(params, vk, circuit.num_instance(), Some(path))
+        }
+    }
+}
+
+fn custom_read_vk<C, P>(
+    fname: P,
+    _: &C,
+    name: &str,
+    degree: u32,
+) -> Result<VerifyingKey<G1Affine>, ScaffoldError>
+where
+    C: Circuit<Fr>,
+    P: AsRef<Path>,
+{
+    keys::read_vk::<G1Affine, C>(fname, name, degree)
 }
 
 impl<T, Fn> PreCircuit for CircuitScaffold<T, Fn>
 where
-    Fn: FnOnce(&mut GateThreadBuilder<Fr>, T, &mut Vec<AssignedValue<Fr>>),
+    T: Clone + 'static,
+    Fn: FnOnce(&mut GateThreadBuilder<Fr>, T, &mut NamedInstances<Fr>) + Clone + 'static,
 {
-    type Pinning = AggregationConfigPinning;
+    type Pinning = ScaffoldConfigPinning;
 
     /// Creates a Halo2 circuit from the given function.
+    ///
+    /// Panics if [`CircuitScaffold::build_circuit`] returns an error, since this trait method cannot return one.
+    /// The runners check the lookup bits and pinning before creating a circuit, so this does not happen through them.
     fn create_circuit(
         self,
         stage: CircuitBuilderStage,
         pinning: Option<Self::Pinning>,
         params: &ParamsKZG<Bn256>,
     ) -> impl PinnableCircuit<Fr> {
+        self.build_circuit::<Fr>(stage, pinning, params.k() as usize)
+            .unwrap_or_else(|e| panic!("{e}"))
+    }
+}
+
+impl<T, Fn> CircuitScaffold<T, Fn> {
+    /// Wraps the circuit function `f` and one input, e.g. to prove it with [`prover::Prover`].
+    ///
+    /// `lookup_bits` and `minimum_rows` are the values of `--lookup-bits` and `--minimum-rows`; `None` uses the defaults at keygen
+    /// and the pinned values afterwards.
+    pub fn new(
+        f: Fn,
+        private_inputs: T,
+        lookup_bits: Option<usize>,
+        minimum_rows: Option<usize>,
+    ) -> Self {
+        Self { f, private_inputs, lookup_bits, minimum_rows, dummy: None }
+    }
+
+    /// Uses [`DummyInput::dummy`] as the placeholder inputs of this circuit, e.g. for [`Circuit::without_witnesses`].
+    pub fn with_dummy(self) -> Self
+    where
+        T: DummyInput,
+    {
+        Self { dummy: Some(T::dummy), ..self }
+    }
+
+    /// Creates a Halo2 circuit over the field `F` with `2^k` rows from the given function.
+    ///
+    /// Unlike [`PreCircuit::create_circuit`], this does not depend on the commitment scheme, so the IPA backend in [`ipa`] uses it too.
+    /// Returns a [`ScaffoldError::Config`] if the lookup table does not fit in `2^k` rows, or if `stage` is the prover stage
+    /// and there is no pinning.
+    pub fn build_circuit<F: ScalarField>(
+        self,
+        stage: CircuitBuilderStage,
+        pinning: Option<ScaffoldConfigPinning>,
+        k: usize,
+    ) -> Result<ScaffoldCircuitBuilder<F>, ScaffoldError>
+    where
+        T: Clone + 'static,
+        Fn: FnOnce(&mut GateThreadBuilder<F>, T, &mut NamedInstances<F>) + Clone + 'static,
+    {
         // we initiate a "thread builder". This is what keeps track of the execution trace of our program. If not in proving mode, it also keeps track of the ZK constraints.
         let mut builder = match stage {
             CircuitBuilderStage::Prover => GateThreadBuilder::new(true),
             _ => GateThreadBuilder::new(false),
         };
-        let k = params.k() as usize;
-        // we use env var `LOOKUP_BITS` to determine whether to use `GateThreadBuilder` or `RangeCircuitBuilder`. The difference is that the latter creates a lookup table with 2^LOOKUP_BITS rows, while the former does not.
-        let lookup_bits: usize = var("LOOKUP_BITS")
-            .map(|str| {
-                let lookup_bits = str.parse().unwrap();
-                // we use a lookup table with 2^LOOKUP_BITS rows. Due to blinding factors, we need a little more than 2^LOOKUP_BITS rows total in our circuit
-                assert!(lookup_bits < k, "LOOKUP_BITS needs to be less than DEGREE");
-                lookup_bits
-            })
-            .unwrap_or(0);
-        // set `LOOKUP_BITS` to '0' if it is not set; this is just a technicality to re-use some other code
-        set_var("LOOKUP_BITS", lookup_bits.to_string());
+        // use the pinned values if there is a pinning, otherwise the ones from the command line
+        let (lookup_bits, minimum_rows) = match &pinning {
+            Some(pinning) => (pinning.lookup_bits(), pinning.minimum_rows()),
+            None => {
+                (self.lookup_bits.unwrap_or(0), self.minimum_rows.unwrap_or(DEFAULT_MINIMUM_ROWS))
+            }
+        };
+        // `lookup_bits` determines whether to use `GateThreadBuilder` or `RangeCircuitBuilder`. The difference is that the latter creates a lookup table with 2^lookup_bits rows, while the former does not.
+        // we use a lookup table with 2^lookup_bits rows. Due to blinding factors, we need a little more than 2^lookup_bits rows total in our circuit
+        if lookup_bits != 0 && lookup_bits >= k {
+            return Err(ScaffoldError::Config(format!(
+                "--lookup-bits {lookup_bits} needs to be less than DEGREE {k}"
+            )));
+        }
+        // the prover stage uses the break points pinned at keygen
+        if stage == CircuitBuilderStage::Prover && pinning.is_none() {
+            return Err(ScaffoldError::Config(
+                "Proving needs the pinning written by keygen".to_string(),
+            ));
+        }
+        // make `lookup_bits` available to the circuit function through `scaffold::lookup_bits()`
+        pinning::set_lookup_bits(lookup_bits);
+        // `without_witnesses` builds the same circuit from the placeholder inputs, or from these inputs if there is none
+        let placeholder = CircuitScaffold {
+            f: self.f.clone(),
+            private_inputs: self.dummy.map_or_else(|| self.private_inputs.clone(), |dummy| dummy()),
+            lookup_bits: Some(lookup_bits),
+            minimum_rows: Some(minimum_rows),
+            dummy: self.dummy,
+        };
+        let without_witnesses = Rc::new(move || {
+            placeholder
+                .clone()
+                .build_circuit::<F>(CircuitBuilderStage::Keygen, None, k)
+                .expect("the placeholder has the lookup bits of a circuit that was already built")
+        });
         // builder.main(phase) gets a default "main" thread for the given phase. For most purposes we only need to think about phase 0
         // we need a 64-bit number as input in this case
-        // while `some_algorithm_in_zk` was written generically for any field `F`, in practice we use the scalar field of the BN254 curve because that's what the proving system backend uses
-        let mut assigned_instances = vec![];
-        (self.f)(&mut builder, self.private_inputs, &mut assigned_instances);
+        // while `some_algorithm_in_zk` was written generically for any field `F`, in practice we use the scalar field of the BN254 curve because that's what the KZG backend uses (the IPA backend uses the Pasta field `Fp`)
+        let mut make_public = NamedInstances::new();
+        clear_instance_names();
+        (self.f)(&mut builder, self.private_inputs, &mut make_public);
+        make_public.record_names();
 
         // now `builder` contains the execution trace, and we are ready to actually create the circuit
         // minimum rows is the number of rows used for blinding factors. This depends on the circuit itself, but we can guess the number and change it if something breaks (default 9 usually works)
-        let minimum_rows = var("MINIMUM_ROWS").unwrap_or_else(|_| "9".to_string()).parse().unwrap();
         // auto-tune circuit
         match stage {
             CircuitBuilderStage::Prover => {}
             _ => {
-                builder.config(k, Some(minimum_rows));
+                let gate = builder.config(k, Some(minimum_rows));
+                if stage == CircuitBuilderStage::Mock {
+                    BuilderStats::new(&builder, &gate).set_current();
+                }
+                ScaffoldConfigParams { gate, lookup_bits, minimum_rows }.set_current();
             }
         };
 
         let circuit = match stage {
             CircuitBuilderStage::Prover => RangeCircuitBuilder::prover(
                 builder,
-                pinning.expect("Circuit pinning not found").break_points(),
+                // checked above
+                pinning.map(Halo2ConfigPinning::break_points).unwrap_or_default(),
             ),
             CircuitBuilderStage::Keygen => RangeCircuitBuilder::keygen(builder),
             CircuitBuilderStage::Mock => RangeCircuitBuilder::mock(builder),
         };
-        ScaffoldCircuitBuilder(RangeWithInstanceCircuitBuilder::new(circuit, assigned_instances))
+        Ok(ScaffoldCircuitBuilder {
+            circuit: RangeWithInstanceCircuitBuilder::new(circuit, make_public.into_values()),
+            without_witnesses,
+        })
     }
 }
 
-pub struct ScaffoldCircuitBuilder<F: ScalarField>(RangeWithInstanceCircuitBuilder<F>);
+/// Largest degree supported by the BN254 scalar field
+const MAX_DEGREE: usize = 28;
+
+impl<T, Fn> CircuitScaffold<T, Fn>
+where
+    Fn: FnOnce(&mut GateThreadBuilder<Fr>, T, &mut NamedInstances<Fr>),
+{
+    /// Runs witness generation once and returns the degree `k` with the lowest [`estimated_cost`].
+    ///
+    /// Every candidate `k` leaves room for the lookup table and the `minimum_rows` blinding rows. Ties go to the smaller `k`.
+    pub fn search_degree(self) -> Result<u32, ScaffoldError> {
+        let lookup_bits = self.lookup_bits.unwrap_or(0);
+        let minimum_rows = self.minimum_rows.unwrap_or(DEFAULT_MINIMUM_ROWS);
+        if lookup_bits >= MAX_DEGREE {
+            return Err(ScaffoldError::Config(format!(
+                "--lookup-bits {lookup_bits} needs to be less than the largest supported degree {MAX_DEGREE}"
+            )));
+        }
+        let mut builder = GateThreadBuilder::new(false);
+        pinning::set_lookup_bits(lookup_bits);
+        clear_instance_names();
+        (self.f)(&mut builder, self.private_inputs, &mut NamedInstances::new());
+        pinning::check_lookup_bits()?;
+
+        let min_k = (1..=MAX_DEGREE)
+            .find(|&k| (1 << k) > (1 << lookup_bits) + minimum_rows)
+            .ok_or_else(|| {
+                ScaffoldError::Config(
+                    "lookup table does not fit in a circuit of any supported degree".to_string(),
+                )
+            })?;
+        let mut best: Option<(usize, u64)> = None;
+        for k in min_k..=MAX_DEGREE {
+            let params = builder.config(k, Some(minimum_rows));
+            let cost = estimated_cost(&params, lookup_bits);
+            log::info!("Degree {k}: estimated cost {cost}");
+            if best.map_or(true, |(_, best_cost)| cost < best_cost) {
+                best = Some((k, cost));
+            }
+            // once everything fits in a single column per phase, more rows only add padding
+            let fits_in_one_column = params
+                .num_advice_per_phase
+                .iter()
+                .chain(params.num_lookup_advice_per_phase.iter())
+                .chain(iter::once(&params.num_fixed))
+                .all(|&num| num <= 1);
+            if fits_in_one_column {
+                break;
+            }
+        }
+        // the loop runs at least once since `min_k <= MAX_DEGREE`
+        Ok(best.unwrap().0 as u32)
+    }
+
+    /// Runs the circuit function in witness generation mode only and returns its public instances and cell counts.
+    ///
+    /// Unlike [`PreCircuit::create_circuit`], no constraints are recorded and no circuit is created, see [`witness`].
+    pub fn generate_witness(self) -> Result<WitnessReport, ScaffoldError> {
+        let mut builder = GateThreadBuilder::new(true);
+        pinning::set_lookup_bits(self.lookup_bits.unwrap_or(0));
+        let mut make_public = NamedInstances::new();
+        clear_instance_names();
+        (self.f)(&mut builder, self.private_inputs, &mut make_public);
+        pinning::check_lookup_bits()?;
+
+        let contexts = builder.threads.iter().flatten();
+        let advice_cells = contexts.clone().map(|ctx| ctx.advice.len()).sum();
+        let lookup_cells = contexts.map(|ctx| ctx.cells_to_lookup.len()).sum();
+        Ok(WitnessReport { advice_cells, lookup_cells, instances: make_public.named()? })
+    }
+}
+
+/// Degree of the constraint system of `halo2-lib` range circuits, which bounds the number of columns per permutation
+/// product and sets the number of quotient pieces
+const CS_DEGREE: usize = 5;
+/// Work per polynomial that does not shrink with the number of rows, e.g. for opening it, in units of one row
+const POLY_OVERHEAD_ROWS: u64 = 1 << 10;
+
+/// A rough estimate of proving cost, in units of the work per row of one polynomial.
+///
+/// The prover commits to the advice columns, to three polynomials per lookup advice column, to one permutation product
+/// per `CS_DEGREE - 2` columns with equality enabled and to `CS_DEGREE - 1` quotient pieces. Besides these, every gate
+/// advice column has a selector column, and the lookup table and instance column are one column each. All of them are
+/// evaluated over the extended domain, which is `CS_DEGREE - 1` times larger, to compute the quotient.
+/// Each polynomial also costs [`POLY_OVERHEAD_ROWS`], so a circuit with few rows and many columns is not cheaper than
+/// the same circuit with fewer columns.
+pub fn estimated_cost(params: &FlexGateConfigParams, lookup_bits: usize) -> u64 {
+    let num_gate_advice: usize = params.num_advice_per_phase.iter().sum();
+    let num_lookup_advice: usize = params.num_lookup_advice_per_phase.iter().sum();
+    let num_advice = num_gate_advice + num_lookup_advice;
+    // the instance column takes part in the permutation, to constrain the public instances
+    let num_equality = num_advice + params.num_fixed + 1;
+    let num_permutation_products = (num_equality + CS_DEGREE - 3) / (CS_DEGREE - 2);
+    let num_committed =
+        num_advice + 3 * num_lookup_advice + num_permutation_products + (CS_DEGREE - 1);
+    let num_polys =
+        num_committed + params.num_fixed + num_gate_advice + usize::from(lookup_bits > 0) + 1;
+    let work_per_row = num_committed + (CS_DEGREE - 1) * num_polys;
+    work_per_row as u64 * ((1u64 << params.k) + POLY_OVERHEAD_ROWS)
+}
+
+pub struct ScaffoldCircuitBuilder<F: ScalarField> {
+    circuit: RangeWithInstanceCircuitBuilder<F>,
+    /// Builds the same circuit in the keygen stage from placeholder inputs
+    without_witnesses: Rc<dyn Fn() -> ScaffoldCircuitBuilder<F>>,
+}
 
 impl<F: ScalarField> Circuit<F> for ScaffoldCircuitBuilder<F> {
     type Config = RangeWithInstanceConfig<F>;
     type FloorPlanner = SimpleFloorPlanner;
 
     fn without_witnesses(&self) -> Self {
-        unimplemented!()
+        (self.without_witnesses)()
     }
 
+    /// Same as `RangeWithInstanceCircuitBuilder::configure`, but reads the configuration from [`ScaffoldConfigParams::current`] instead of environment variables.
     fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
-        RangeWithInstanceCircuitBuilder::configure(meta)
+        let ScaffoldConfigParams { gate, lookup_bits, .. } = ScaffoldConfigParams::current();
+        let FlexGateConfigParams {
+            k,
+            num_advice_per_phase,
+            num_lookup_advice_per_phase,
+            num_fixed,
+            ..
+        } = gate;
+        let range = RangeConfig::configure(
+            meta,
+            RangeStrategy::Vertical,
+            &num_advice_per_phase,
+            &num_lookup_advice_per_phase,
+            num_fixed,
+            lookup_bits,
+            k,
+        );
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+        RangeWithInstanceConfig { range, instance }
     }
 
     fn synthesize(&self, config: Self::Config, layouter: impl Layouter<F>) -> Result<(), Error> {
-        self.0.synthesize(config, layouter)
+        self.circuit.synthesize(config, layouter)
     }
 }
 
 impl<F: ScalarField> CircuitExt<F> for ScaffoldCircuitBuilder<F> {
     fn num_instance(&self) -> Vec<usize> {
-        vec![self.0.assigned_instances.len()]
+        vec![self.circuit.assigned_instances.len()]
     }
 
     fn instances(&self) -> Vec<Vec<F>> {
-        self.0.instances()
+        self.circuit.instances()
     }
 }
 
 impl<F: ScalarField> PinnableCircuit<F> for ScaffoldCircuitBuilder<F> {
-    type Pinning = AggregationConfigPinning;
+    type Pinning = ScaffoldConfigPinning;
 
     fn break_points(&self) -> MultiPhaseThreadBreakPoints {
-        self.0.circuit.0.break_points.borrow().clone()
+        self.circuit.circuit.0.break_points.borrow().clone()
     }
 }
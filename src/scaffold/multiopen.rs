@@ -0,0 +1,52 @@
+//! The KZG multiopen scheme used to open the polynomial commitments of a proof, selected with `--multiopen` at keygen.
+//!
+//! SHPLONK proofs are smaller and cheaper to verify in the EVM, while GWC proofs are faster to create.
+//! The choice does not change the proving or verifying key, but the Solidity verifier and every proof depend on it,
+//! so keygen records it in the pinning file and `prove` and `verify` read it from there.
+use std::path::Path;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use super::{error::ScaffoldError, pinning::read_pinning_field};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum MultiOpenKind {
+    #[default]
+    Shplonk,
+    Gwc,
+}
+
+impl std::fmt::Display for MultiOpenKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Shplonk => write!(f, "shplonk"),
+            Self::Gwc => write!(f, "gwc"),
+        }
+    }
+}
+
+/// The field of the pinning file holding the multiopen scheme
+pub(crate) const MULTIOPEN_FIELD: &str = "multiopen";
+
+/// Reads the multiopen scheme recorded in the pinning file at `pinning_path`.
+///
+/// Pinnings written before the scheme was recorded are SHPLONK, the only scheme supported at the time.
+pub fn read_multiopen(pinning_path: impl AsRef<Path>) -> Result<MultiOpenKind, ScaffoldError> {
+    Ok(read_pinning_field(pinning_path, MULTIOPEN_FIELD)?.unwrap_or(MultiOpenKind::Shplonk))
+}
+
+/// The multiopen scheme pinned at `pinning_path`. Returns an error if `multiopen` was specified and differs from it.
+pub fn pinned_multiopen(
+    pinning_path: impl AsRef<Path>,
+    multiopen: Option<MultiOpenKind>,
+) -> Result<MultiOpenKind, ScaffoldError> {
+    let pinned = read_multiopen(pinning_path)?;
+    match multiopen {
+        Some(multiopen) if multiopen != pinned => Err(ScaffoldError::Config(format!(
+            "--multiopen {multiopen} does not match the scheme the keys were generated with ({pinned})"
+        ))),
+        _ => Ok(pinned),
+    }
+}
@@ -0,0 +1,458 @@
+//! Circuit configuration pinned at keygen and read back by the prover and verifier.
+//!
+//! `halo2-lib` passes circuit configuration to `Circuit::configure` through process-wide environment variables.
+//! For circuits created with [`super::CircuitScaffold`] we instead keep the configuration in thread-local storage,
+//! so several circuits with different lookup bits can be built in parallel (e.g., in tests) without interfering.
+//! [`super::EthScaffold`] circuits do the same with [`EthScaffoldPinning`]. Computing their configuration at keygen still
+//! goes through `EthCircuitBuilder::config`, which reads and writes environment variables; [`with_config_env`] runs it
+//! under a lock and restores the variables afterwards. `axiom-eth` also passes the keccak configuration through
+//! environment variables while the circuit is configured and synthesized, so eth circuits with different keccak
+//! configurations must not be proved at the same time on different threads of one process.
+use std::{
+    cell::{Cell, RefCell},
+    env,
+    fs::{self, File},
+    io::BufReader,
+    path::Path,
+    sync::Mutex,
+};
+
+use axiom_eth::{
+    rlp::builder::RlcThreadBreakPoints,
+    util::{AggregationConfigPinning, Halo2ConfigPinning},
+    EthConfigParams, ETH_LOOKUP_BITS,
+};
+use halo2_base::gates::{
+    builder::{FlexGateConfigParams, MultiPhaseThreadBreakPoints},
+    flex_gate::GateStrategy,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use super::error::ScaffoldError;
+
+/// Default number of rows reserved for blinding factors in [`super::CircuitScaffold`] circuits.
+pub const DEFAULT_MINIMUM_ROWS: usize = 9;
+/// Default number of rows reserved for blinding factors in [`super::EthScaffold`] circuits.
+pub const ETH_DEFAULT_MINIMUM_ROWS: usize = 109;
+/// Number of rows reserved for blinding factors in aggregation circuits. This is not configurable, since `AggregationConfigPinning` does not record it.
+pub const AGG_MINIMUM_ROWS: usize = 20;
+
+/// Environment variables `axiom-eth` and `halo2-lib` read or write while computing a circuit configuration
+const CONFIG_ENV_VARS: [&str; 6] = [
+    "LOOKUP_BITS",
+    "FLEX_GATE_CONFIG_PARAMS",
+    "ETH_CONFIG_PARAMS",
+    "KECCAK_ROWS",
+    "KECCAK_DEGREE",
+    "UNUSABLE_ROWS",
+];
+
+/// Held by [`with_config_env`] while the environment variables are changed
+static CONFIG_ENV_LOCK: Mutex<()> = Mutex::new(());
+
+thread_local! {
+    static LOOKUP_BITS: Cell<usize> = Cell::new(0);
+    /// Set when the circuit function asked for [`required_lookup_bits`] without a lookup table
+    static LOOKUP_BITS_MISSING: Cell<bool> = Cell::new(false);
+    static CONFIG_PARAMS: RefCell<Option<ScaffoldConfigParams>> = RefCell::new(None);
+    static ETH_CONFIG_PARAMS: RefCell<Option<EthConfigParams>> = RefCell::new(None);
+}
+
+/// The number of bits of the lookup table of the circuit currently being built on this thread.
+///
+/// Use this inside your circuit function to create a `RangeChip`, e.g. `RangeChip::default(lookup_bits())`.
+/// Circuits that range check with the lookup table should use [`required_lookup_bits`] instead.
+pub fn lookup_bits() -> usize {
+    LOOKUP_BITS.with(|bits| bits.get())
+}
+
+/// Same as [`lookup_bits`], for circuit functions that range check with the lookup table.
+///
+/// If the circuit has no lookup table, the runner returns a [`ScaffoldError::Config`] asking for `--lookup-bits` once the
+/// circuit function returns. So that the circuit function can still run to completion, this returns 1 in that case.
+pub fn required_lookup_bits() -> usize {
+    match lookup_bits() {
+        0 => {
+            LOOKUP_BITS_MISSING.with(|missing| missing.set(true));
+            1
+        }
+        bits => bits,
+    }
+}
+
+pub(crate) fn set_lookup_bits(lookup_bits: usize) {
+    LOOKUP_BITS.with(|bits| bits.set(lookup_bits));
+    LOOKUP_BITS_MISSING.with(|missing| missing.set(false));
+}
+
+/// Returns an error if the circuit function last run on this thread called [`required_lookup_bits`] without a lookup table.
+pub(crate) fn check_lookup_bits() -> Result<(), ScaffoldError> {
+    if LOOKUP_BITS_MISSING.with(|missing| missing.get()) {
+        return Err(ScaffoldError::Config(
+            "This circuit range checks with a lookup table; set --lookup-bits to a value between 1 and DEGREE - 1"
+                .to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScaffoldConfigParams {
+    pub gate: FlexGateConfigParams,
+    /// The lookup table has `2^lookup_bits` rows. `0` means there is no lookup table.
+    pub lookup_bits: usize,
+    /// Number of rows at the end of the circuit reserved for blinding factors
+    pub minimum_rows: usize,
+}
+
+impl ScaffoldConfigParams {
+    /// Makes these the parameters used to configure [`super::ScaffoldCircuitBuilder`] on the current thread.
+    pub fn set_current(&self) {
+        set_lookup_bits(self.lookup_bits);
+        CONFIG_PARAMS.with(|params| *params.borrow_mut() = Some(self.clone()));
+    }
+
+    /// The parameters last set on the current thread by [`Self::set_current`].
+    pub fn try_current() -> Result<Self, ScaffoldError> {
+        CONFIG_PARAMS.with(|params| params.borrow().clone()).ok_or_else(config_not_set)
+    }
+
+    /// Same as [`Self::try_current`], for `Circuit::configure` and `Halo2ConfigPinning::from_var`, which cannot return an error.
+    ///
+    /// The runners always set the configuration before either is called, so this only panics when a circuit is
+    /// configured directly, e.g. by reading its keys, without calling `set_var` on its pinning first.
+    pub fn current() -> Self {
+        Self::try_current().unwrap_or_else(|e| panic!("{e}"))
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(from = "ConfigPinningShape")]
+pub struct ScaffoldConfigPinning {
+    pub params: ScaffoldConfigParams,
+    pub break_points: MultiPhaseThreadBreakPoints,
+}
+
+/// The shapes of pinning file that can be read as a [`ScaffoldConfigPinning`].
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ConfigPinningShape {
+    Current {
+        params: ScaffoldConfigParams,
+        break_points: MultiPhaseThreadBreakPoints,
+    },
+    /// Written by keygen before the lookup bits and minimum rows were pinned, when circuits were pinned like aggregation circuits
+    Legacy(AggregationConfigPinning),
+}
+
+impl From<ConfigPinningShape> for ScaffoldConfigPinning {
+    fn from(shape: ConfigPinningShape) -> Self {
+        match shape {
+            ConfigPinningShape::Current { params, break_points } => Self { params, break_points },
+            ConfigPinningShape::Legacy(AggregationConfigPinning { params, break_points }) => {
+                let gate = FlexGateConfigParams {
+                    strategy: GateStrategy::Vertical,
+                    k: params.degree as usize,
+                    num_advice_per_phase: vec![params.num_advice],
+                    num_lookup_advice_per_phase: vec![params.num_lookup_advice],
+                    num_fixed: params.num_fixed,
+                };
+                // the minimum rows were not pinned; this was also the default of the `MINIMUM_ROWS` environment variable
+                let minimum_rows = DEFAULT_MINIMUM_ROWS;
+                let params =
+                    ScaffoldConfigParams { gate, lookup_bits: params.lookup_bits, minimum_rows };
+                Self { params, break_points }
+            }
+        }
+    }
+}
+
+impl Halo2ConfigPinning for ScaffoldConfigPinning {
+    type BreakPoints = MultiPhaseThreadBreakPoints;
+
+    /// Sets the thread-local configuration; no environment variables are touched.
+    fn set_var(&self) {
+        self.params.set_current();
+    }
+
+    fn break_points(self) -> MultiPhaseThreadBreakPoints {
+        self.break_points
+    }
+
+    fn from_var(break_points: MultiPhaseThreadBreakPoints) -> Self {
+        Self { params: ScaffoldConfigParams::current(), break_points }
+    }
+
+    fn degree(&self) -> u32 {
+        self.params.gate.k as u32
+    }
+}
+
+/// Runs `config`, which configures a circuit through environment variables, with `LOOKUP_BITS` set to `lookup_bits`.
+///
+/// Calls are serialized under a lock and the variables are restored afterwards, so circuits configured on different
+/// threads, e.g. by `serve --circuits a,b`, neither see nor overwrite each other's values.
+pub(crate) fn with_config_env<R>(lookup_bits: usize, config: impl FnOnce() -> R) -> R {
+    let _lock = CONFIG_ENV_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let saved = CONFIG_ENV_VARS.map(|key| (key, env::var_os(key)));
+    env::set_var("LOOKUP_BITS", lookup_bits.to_string());
+    let result = config();
+    for (key, value) in saved {
+        match value {
+            Some(value) => env::set_var(key, value),
+            None => env::remove_var(key),
+        }
+    }
+    result
+}
+
+/// Sets the parameters used to configure [`super::EthScaffold`] circuits on the current thread, including the lookup bits.
+pub(crate) fn set_eth_config_params(params: &EthConfigParams) {
+    set_lookup_bits(params.lookup_bits.unwrap_or(ETH_LOOKUP_BITS));
+    ETH_CONFIG_PARAMS.with(|current| *current.borrow_mut() = Some(params.clone()));
+}
+
+/// The parameters last set on the current thread by [`set_eth_config_params`].
+pub(crate) fn try_eth_config_params() -> Result<EthConfigParams, ScaffoldError> {
+    ETH_CONFIG_PARAMS.with(|params| params.borrow().clone()).ok_or_else(config_not_set)
+}
+
+/// Same as [`try_eth_config_params`], for `Circuit::configure` and `Halo2ConfigPinning::from_var`, see [`ScaffoldConfigParams::current`].
+pub(crate) fn eth_config_params() -> EthConfigParams {
+    try_eth_config_params().unwrap_or_else(|e| panic!("{e}"))
+}
+
+fn config_not_set() -> ScaffoldError {
+    ScaffoldError::Config(
+        "Circuit configuration has not been set on this thread; build the circuit or call `set_var` on its pinning first"
+            .to_string(),
+    )
+}
+
+/// Same JSON as `EthConfigPinning`, so existing pinning files can be read, but the configuration is kept in thread-local storage.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EthScaffoldPinning {
+    pub params: EthConfigParams,
+    pub break_points: RlcThreadBreakPoints,
+}
+
+impl Halo2ConfigPinning for EthScaffoldPinning {
+    type BreakPoints = RlcThreadBreakPoints;
+
+    /// Sets the thread-local configuration; unlike `EthConfigPinning`, `LOOKUP_BITS` and `ETH_CONFIG_PARAMS` are not touched.
+    fn set_var(&self) {
+        set_eth_config_params(&self.params);
+    }
+
+    fn break_points(self) -> RlcThreadBreakPoints {
+        self.break_points
+    }
+
+    fn from_var(break_points: RlcThreadBreakPoints) -> Self {
+        Self { params: eth_config_params(), break_points }
+    }
+
+    fn degree(&self) -> u32 {
+        self.params.degree
+    }
+}
+
+/// Reads the pinning written by `keygen` from `path`.
+///
+/// Unlike `Halo2ConfigPinning::from_path`, this returns an error instead of panicking and does not call `set_var`.
+pub fn read_pinning<P: Halo2ConfigPinning>(path: impl AsRef<Path>) -> Result<P, ScaffoldError> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Err(ScaffoldError::MissingPinning(path.to_path_buf()));
+    }
+    let f = File::open(path).map_err(ScaffoldError::io(path))?;
+    serde_json::from_reader(BufReader::new(f)).map_err(ScaffoldError::parse(path))
+}
+
+/// Reads the pinning file written by keygen at `path` as JSON, including the fields recorded with [`set_pinning_field`].
+pub fn read_pinning_json(path: impl AsRef<Path>) -> Result<Value, ScaffoldError> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Err(ScaffoldError::MissingPinning(path.to_path_buf()));
+    }
+    let contents = fs::read(path).map_err(ScaffoldError::io(path))?;
+    serde_json::from_slice(&contents).map_err(ScaffoldError::parse(path))
+}
+
+/// Writes `pinning`, e.g. from [`super::prover::Prover::pinning`], to the pinning file at `path`.
+pub fn write_pinning_json(path: impl AsRef<Path>, pinning: &Value) -> Result<(), ScaffoldError> {
+    let path = path.as_ref();
+    let contents = serde_json::to_vec_pretty(pinning).map_err(ScaffoldError::parse(path))?;
+    fs::write(path, contents).map_err(ScaffoldError::io(path))
+}
+
+/// Records `value` as the field `key` of `pinning`.
+///
+/// This is how keygen records what `Halo2ConfigPinning` has no place for, such as the multiopen scheme.
+pub fn set_pinning_field<V: Serialize>(
+    pinning: &mut Value,
+    key: &str,
+    value: &V,
+) -> Result<(), String> {
+    let fields = pinning.as_object_mut().ok_or("pinning is not a JSON object")?;
+    fields.insert(key.to_string(), serde_json::to_value(value).map_err(|e| e.to_string())?);
+    Ok(())
+}
+
+/// Reads the field `key` recorded with [`set_pinning_field`]. Returns `None` if the pinning was written before the field was recorded.
+pub fn pinning_field<V: DeserializeOwned>(pinning: &Value, key: &str) -> Result<Option<V>, String> {
+    pinning.get(key).map(|value| V::deserialize(value).map_err(|e| e.to_string())).transpose()
+}
+
+/// Records `value` as the field `key` of the pinning file at `path`, which must already have been written by keygen.
+pub fn write_pinning_field<V: Serialize>(
+    path: impl AsRef<Path>,
+    key: &str,
+    value: &V,
+) -> Result<(), ScaffoldError> {
+    let path = path.as_ref();
+    let mut pinning = read_pinning_json(path)?;
+    set_pinning_field(&mut pinning, key, value)
+        .map_err(|reason| ScaffoldError::Parse { path: path.to_path_buf(), reason })?;
+    write_pinning_json(path, &pinning)
+}
+
+/// Reads the field `key` recorded with [`write_pinning_field`] from the pinning file at `path`.
+pub fn read_pinning_field<V: DeserializeOwned>(
+    path: impl AsRef<Path>,
+    key: &str,
+) -> Result<Option<V>, ScaffoldError> {
+    let path = path.as_ref();
+    pinning_field(&read_pinning_json(path)?, key)
+        .map_err(|reason| ScaffoldError::Parse { path: path.to_path_buf(), reason })
+}
+
+/// A pinning that records the lookup bits and minimum rows chosen at keygen.
+pub trait ScaffoldPinning: Halo2ConfigPinning {
+    fn lookup_bits(&self) -> usize;
+
+    fn minimum_rows(&self) -> usize;
+
+    /// Returns an error if `lookup_bits` or `minimum_rows` were specified and differ from the pinned values.
+    fn check_matches(
+        &self,
+        lookup_bits: Option<usize>,
+        minimum_rows: Option<usize>,
+    ) -> Result<(), ScaffoldError> {
+        if let Some(lookup_bits) = lookup_bits.filter(|&bits| bits != self.lookup_bits()) {
+            return Err(ScaffoldError::Config(format!(
+                "--lookup-bits {lookup_bits} does not match the value the keys were generated with ({})",
+                self.lookup_bits()
+            )));
+        }
+        if let Some(minimum_rows) = minimum_rows.filter(|&rows| rows != self.minimum_rows()) {
+            return Err(ScaffoldError::Config(format!(
+                "--minimum-rows {minimum_rows} does not match the value the keys were generated with ({})",
+                self.minimum_rows()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns an error if the keys were not generated for circuits with `2^k` rows.
+    fn check_degree(&self, k: u32) -> Result<(), ScaffoldError> {
+        if k != self.degree() {
+            return Err(ScaffoldError::Config(format!(
+                "DEGREE {k} does not match the value the keys were generated with ({})",
+                self.degree()
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl ScaffoldPinning for ScaffoldConfigPinning {
+    fn lookup_bits(&self) -> usize {
+        self.params.lookup_bits
+    }
+
+    fn minimum_rows(&self) -> usize {
+        self.params.minimum_rows
+    }
+}
+
+impl ScaffoldPinning for EthScaffoldPinning {
+    fn lookup_bits(&self) -> usize {
+        self.params.lookup_bits.unwrap_or(ETH_LOOKUP_BITS)
+    }
+
+    fn minimum_rows(&self) -> usize {
+        self.params.unusable_rows
+    }
+}
+
+impl ScaffoldPinning for AggregationConfigPinning {
+    fn lookup_bits(&self) -> usize {
+        self.params.lookup_bits
+    }
+
+    fn minimum_rows(&self) -> usize {
+        AGG_MINIMUM_ROWS
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use halo2_base::gates::{builder::FlexGateConfigParams, flex_gate::GateStrategy};
+
+    use std::env;
+
+    use super::{with_config_env, ScaffoldConfigParams, ScaffoldConfigPinning, ScaffoldPinning};
+
+    fn pinning(lookup_bits: usize, minimum_rows: usize) -> ScaffoldConfigPinning {
+        let gate = FlexGateConfigParams {
+            strategy: GateStrategy::Vertical,
+            k: 10,
+            num_advice_per_phase: vec![1],
+            num_lookup_advice_per_phase: vec![1],
+            num_fixed: 1,
+        };
+        let params = ScaffoldConfigParams { gate, lookup_bits, minimum_rows };
+        ScaffoldConfigPinning { params, break_points: vec![vec![]] }
+    }
+
+    #[test]
+    fn test_check_matches() {
+        let pinning = pinning(8, 9);
+        assert!(pinning.check_matches(None, None).is_ok());
+        assert!(pinning.check_matches(Some(8), Some(9)).is_ok());
+        assert!(pinning.check_matches(Some(7), None).is_err());
+        assert!(pinning.check_matches(None, Some(10)).is_err());
+        assert!(pinning.check_degree(10).is_ok());
+        assert!(pinning.check_degree(11).is_err());
+    }
+
+    #[test]
+    fn test_read_legacy_pinning() {
+        let json = r#"{
+            "params": { "degree": 10, "num_advice": 2, "num_lookup_advice": 1, "num_fixed": 1, "lookup_bits": 8 },
+            "break_points": [[500]]
+        }"#;
+        let pinning: ScaffoldConfigPinning = serde_json::from_str(json).unwrap();
+        assert_eq!(pinning.params.gate.k, 10);
+        assert_eq!(pinning.params.gate.num_advice_per_phase, vec![2]);
+        assert_eq!(pinning.lookup_bits(), 8);
+        assert_eq!(pinning.break_points, vec![vec![500]]);
+
+        let json = serde_json::to_string(&pinning).unwrap();
+        let pinning: ScaffoldConfigPinning = serde_json::from_str(&json).unwrap();
+        assert_eq!(pinning.params.gate.num_lookup_advice_per_phase, vec![1]);
+    }
+
+    #[test]
+    fn test_config_env_is_restored() {
+        let before = (env::var_os("LOOKUP_BITS"), env::var_os("FLEX_GATE_CONFIG_PARAMS"));
+        let seen = with_config_env(11, || {
+            env::set_var("FLEX_GATE_CONFIG_PARAMS", "written by config");
+            env::var("LOOKUP_BITS").unwrap()
+        });
+        assert_eq!(seen, "11");
+        assert_eq!((env::var_os("LOOKUP_BITS"), env::var_os("FLEX_GATE_CONFIG_PARAMS")), before);
+    }
+}
@@ -0,0 +1,292 @@
+//! Progress reporting and cancellation for [`super::prover::Prover`].
+//!
+//! halo2's `create_proof` runs as a single call, so its stages are observed through the transcript it writes to:
+//! it first commits to the advice columns phase by phase, then to the lookup, permutation and quotient polynomials,
+//! and finally writes the evaluations and opening proof. [`TranscriptLayout`] counts these commitments from the
+//! constraint system, and [`ObservedTranscript`] reports a new [`ProofStage`] when the writes of a stage start. It
+//! also fails the next write once the proof is cancelled, which makes `create_proof` return early.
+use std::{
+    fmt, io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+        Arc,
+    },
+};
+
+use halo2_base::halo2_proofs::{
+    arithmetic::{CurveAffine, Field},
+    plonk::{self, ConstraintSystem},
+    transcript::{EncodedChallenge, Transcript, TranscriptWrite},
+};
+use serde::{Deserialize, Serialize};
+
+use super::{error::ScaffoldError, stats::num_permutation_sets};
+
+/// A stage of proving, in the order they are reported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProofStage {
+    /// Checking the input against the circuit shape and generating the witness
+    Witness,
+    /// Assigning the witness and committing to the advice columns
+    AdviceCommitment,
+    /// Committing to the lookup and permutation arguments and the quotient polynomial
+    Quotient,
+    /// Evaluating the polynomials and creating the multiopen proof
+    Openings,
+}
+
+impl fmt::Display for ProofStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Witness => write!(f, "witness generation"),
+            Self::AdviceCommitment => write!(f, "advice commitment"),
+            Self::Quotient => write!(f, "quotient"),
+            Self::Openings => write!(f, "openings"),
+        }
+    }
+}
+
+/// Stops a proof in progress. Clones share the same state, so one clone can be kept by a scheduler
+/// while another is passed to the prover.
+///
+/// The prover only looks at the token when a stage starts and when it writes to the transcript. Witness generation,
+/// and the FFTs and multi-scalar multiplications between two writes (notably computing the quotient polynomial and
+/// the opening proof) run to completion, so a cancelled proof can take as long as its slowest such step to stop.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes the proof return [`ScaffoldError::Cancelled`] at its next check.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Where the stages of one proof are reported, and the token that cancels it.
+///
+/// Cancellation is checked when each stage starts and whenever the prover writes to the transcript,
+/// see [`CancellationToken`] for what this leaves uninterrupted.
+pub struct ProveProgress<'a> {
+    on_stage: Box<dyn FnMut(ProofStage) + 'a>,
+    cancellation: CancellationToken,
+}
+
+impl<'a> ProveProgress<'a> {
+    /// Calls `on_stage` when each stage starts.
+    pub fn new(on_stage: impl FnMut(ProofStage) + 'a, cancellation: CancellationToken) -> Self {
+        Self { on_stage: Box::new(on_stage), cancellation }
+    }
+
+    /// Sends each stage to `sender` when it starts. Stages are dropped once the receiver is gone.
+    pub fn channel(sender: Sender<ProofStage>, cancellation: CancellationToken) -> Self {
+        Self::new(
+            move |stage| {
+                let _ = sender.send(stage);
+            },
+            cancellation,
+        )
+    }
+
+    /// Reports nothing and is never cancelled.
+    pub fn none() -> Self {
+        Self::new(|_| {}, CancellationToken::new())
+    }
+
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
+    pub(crate) fn check(&self) -> Result<(), ScaffoldError> {
+        if self.cancellation.is_cancelled() {
+            return Err(ScaffoldError::Cancelled);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn enter(&mut self, stage: ProofStage) -> Result<(), ScaffoldError> {
+        self.check()?;
+        (self.on_stage)(stage);
+        Ok(())
+    }
+
+    /// Converts an error of `create_proof` into [`ScaffoldError::Cancelled`] if it was caused by cancelling the proof.
+    pub(crate) fn proof_error(&self, e: plonk::Error) -> ScaffoldError {
+        match self.check() {
+            Err(cancelled) => cancelled,
+            Ok(()) => ScaffoldError::Synthesis(e),
+        }
+    }
+}
+
+/// Prints each stage as it starts, as the command line runners do. Never cancelled.
+pub fn print_progress() -> ProveProgress<'static> {
+    ProveProgress::new(|stage| println!("Proving: {stage}"), CancellationToken::new())
+}
+
+impl Default for ProveProgress<'_> {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+/// Where the stages of `create_proof` start in its transcript, counted in points written before them.
+///
+/// Only KZG is used, so instances are absorbed as scalars rather than committed to, and nothing is written between
+/// the commitments besides these points. For each phase, `create_proof` commits to the advice columns of that phase
+/// and then squeezes its challenges, which writes nothing. It then commits to the permuted input and table of every
+/// lookup, the permutation products, the lookup products, the random polynomial of the vanishing argument and the
+/// pieces of the quotient polynomial, and only then writes the evaluations.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct TranscriptLayout {
+    /// Number of advice columns committed in each phase, in order
+    advice_per_phase: Vec<usize>,
+    /// Number of lookup, permutation and vanishing commitments, up to the last piece of the quotient polynomial
+    quotient_commitments: usize,
+}
+
+impl TranscriptLayout {
+    pub fn new<F: Field>(cs: &ConstraintSystem<F>) -> Self {
+        let phases = cs.advice_column_phase();
+        let num_phases = phases.iter().max().map_or(0, |phase| *phase as usize + 1);
+        let mut advice_per_phase = vec![0; num_phases];
+        for phase in phases {
+            advice_per_phase[phase as usize] += 1;
+        }
+        let num_lookups = cs.lookups().len();
+        let num_quotient_pieces = cs.degree() - 1;
+        let quotient_commitments =
+            3 * num_lookups + num_permutation_sets(cs) + 1 + num_quotient_pieces;
+        Self { advice_per_phase, quotient_commitments }
+    }
+
+    /// The points written before each stage after [`ProofStage::AdviceCommitment`] starts.
+    fn boundaries(&self) -> [(usize, ProofStage); 2] {
+        let advice_commitments = self.advice_per_phase.iter().sum::<usize>();
+        [
+            (advice_commitments, ProofStage::Quotient),
+            (advice_commitments + self.quotient_commitments, ProofStage::Openings),
+        ]
+    }
+}
+
+/// A transcript writing to `inner` while reporting the stages of `create_proof` to `progress`.
+///
+/// A stage starts once the points of the stages before it, as counted by [`TranscriptLayout`], have been written.
+pub(crate) struct ObservedTranscript<'t, 'a, T> {
+    inner: &'t mut T,
+    progress: &'t mut ProveProgress<'a>,
+    /// The stages still to come, with the number of points written before each
+    next_stages: Vec<(usize, ProofStage)>,
+    points_written: usize,
+}
+
+impl<'t, 'a, T> ObservedTranscript<'t, 'a, T> {
+    /// Reports [`ProofStage::AdviceCommitment`], which `create_proof` starts with.
+    pub fn new(
+        inner: &'t mut T,
+        progress: &'t mut ProveProgress<'a>,
+        layout: TranscriptLayout,
+    ) -> Result<Self, ScaffoldError> {
+        progress.enter(ProofStage::AdviceCommitment)?;
+        let mut next_stages = layout.boundaries().to_vec();
+        next_stages.reverse();
+        let mut transcript = Self { inner, progress, next_stages, points_written: 0 };
+        // a stage without any commitment before it, e.g. `Quotient` for a circuit without advice, starts right away
+        transcript.enter_reached()?;
+        Ok(transcript)
+    }
+
+    /// Enters every stage whose points have all been written.
+    fn enter_reached(&mut self) -> Result<(), ScaffoldError> {
+        while let Some(&(start, stage)) = self.next_stages.last() {
+            if start > self.points_written {
+                break;
+            }
+            self.next_stages.pop();
+            self.progress.enter(stage)?;
+        }
+        Ok(())
+    }
+
+    fn check(&self) -> io::Result<()> {
+        self.progress.check().map_err(cancelled)
+    }
+}
+
+fn cancelled(e: ScaffoldError) -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, e.to_string())
+}
+
+impl<C, E, T> Transcript<C, E> for ObservedTranscript<'_, '_, T>
+where
+    C: CurveAffine,
+    E: EncodedChallenge<C>,
+    T: TranscriptWrite<C, E>,
+{
+    fn squeeze_challenge(&mut self) -> E {
+        self.inner.squeeze_challenge()
+    }
+
+    fn common_point(&mut self, point: C) -> io::Result<()> {
+        self.check()?;
+        self.inner.common_point(point)
+    }
+
+    fn common_scalar(&mut self, scalar: C::Scalar) -> io::Result<()> {
+        self.check()?;
+        self.inner.common_scalar(scalar)
+    }
+}
+
+impl<C, E, T> TranscriptWrite<C, E> for ObservedTranscript<'_, '_, T>
+where
+    C: CurveAffine,
+    E: EncodedChallenge<C>,
+    T: TranscriptWrite<C, E>,
+{
+    fn write_point(&mut self, point: C) -> io::Result<()> {
+        self.check()?;
+        self.inner.write_point(point)?;
+        self.points_written += 1;
+        self.enter_reached().map_err(cancelled)
+    }
+
+    fn write_scalar(&mut self, scalar: C::Scalar) -> io::Result<()> {
+        self.check()?;
+        self.inner.write_scalar(scalar)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use halo2_base::halo2_proofs::{
+        halo2curves::bn256::Fr,
+        plonk::{ConstraintSystem, SecondPhase},
+    };
+
+    use super::{ProofStage, TranscriptLayout};
+
+    #[test]
+    fn test_transcript_layout() {
+        let mut cs = ConstraintSystem::<Fr>::default();
+        let a = cs.advice_column();
+        cs.advice_column();
+        cs.advice_column_in(SecondPhase);
+        cs.enable_equality(a);
+
+        let layout = TranscriptLayout::new(&cs);
+        assert_eq!(layout.advice_per_phase, vec![2, 1]);
+        // one permutation set, the random polynomial and `degree - 1 = 2` quotient pieces
+        assert_eq!(layout.boundaries(), [(3, ProofStage::Quotient), (7, ProofStage::Openings)]);
+    }
+}
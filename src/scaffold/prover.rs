@@ -0,0 +1,296 @@
+//! Proving without the command line or the filesystem, for embedding the scaffold in services.
+//!
+//! The runners (`run`, `run_builder`, `run_eth`, `run_cli`) parse a [`super::cmd::Cli`] and work through files under
+//! `configs/` and `data/`. A [`Prover`] instead holds the params, proving key and pinning of one circuit in memory,
+//! takes each input as a [`PreCircuit`] and returns the proof and its public instances as a [`ProofBundle`].
+//! A service can load everything once and then prove requests without reading or writing any files.
+//! Proofs are verified with [`super::verifier::Verifier`]. The runners are a thin layer over both.
+use std::{fs::File, io, marker::PhantomData, path::Path};
+
+use axiom_eth::util::{
+    circuit::{PinnableCircuit, PreCircuit},
+    Halo2ConfigPinning,
+};
+use halo2_base::{
+    gates::builder::CircuitBuilderStage,
+    halo2_proofs::{
+        halo2curves::bn256::{Bn256, Fr, G1Affine},
+        plonk::ProvingKey,
+        poly::{commitment::Params, kzg::commitment::ParamsKZG},
+    },
+};
+use serde_json::Value;
+use snark_verifier_sdk::{
+    gen_pk,
+    snark_verifier::system::halo2::{compile, Config},
+    CircuitExt, Snark,
+};
+
+use super::{
+    aggregatable,
+    bundle::{vk_digest, ProofBundle},
+    error::ScaffoldError,
+    fingerprint::{CircuitFingerprint, FINGERPRINT_FIELD},
+    instances::check_instance_names,
+    multiopen::{MultiOpenKind, MULTIOPEN_FIELD},
+    pinning::{check_lookup_bits, pinning_field, set_pinning_field},
+    progress::{ProofStage, ProveProgress},
+    transcript::{gen_proof, TranscriptKind},
+    verifier::{verify_bundle, Verifier},
+};
+
+/// Proves inputs of one circuit, with everything keygen produces held in memory.
+///
+/// `P` is the type of the inputs, e.g. a [`super::CircuitScaffold`] wrapping the circuit function and one input.
+pub struct Prover<P: PreCircuit> {
+    name: String,
+    params: ParamsKZG<Bn256>,
+    pk: ProvingKey<G1Affine>,
+    /// The contents of the pinning file, including the fingerprint and multiopen scheme recorded by keygen
+    pinning: Value,
+    multiopen: MultiOpenKind,
+    /// See [`vk_digest`]
+    vk_digest: String,
+    /// Whether to check each input against the fingerprint of keygen before proving, see [`Self::with_shape_check`]
+    check_shape: bool,
+    _precircuit: PhantomData<fn(P)>,
+}
+
+impl<P> Prover<P>
+where
+    P: PreCircuit + Clone,
+{
+    /// Generates the proving key and pinning for the circuit of `precircuit`, named `name`.
+    ///
+    /// Keygen only needs the shape of the circuit, so `precircuit` can hold placeholder inputs of the right shape.
+    pub fn keygen(
+        name: &str,
+        params: ParamsKZG<Bn256>,
+        precircuit: P,
+        multiopen: MultiOpenKind,
+    ) -> Result<Self, ScaffoldError> {
+        let circuit = precircuit.create_circuit(CircuitBuilderStage::Keygen, None, &params);
+        check_lookup_bits()?;
+        Self::from_keygen_circuit(name, params, &circuit, multiopen)
+    }
+
+    /// Same as [`Self::keygen`], for a circuit already created from a `P` in the keygen stage.
+    ///
+    /// The caller keeps `circuit`, e.g. to write the keys or the Solidity verifier, which need its type.
+    pub fn from_keygen_circuit<C>(
+        name: &str,
+        params: ParamsKZG<Bn256>,
+        circuit: &C,
+        multiopen: MultiOpenKind,
+    ) -> Result<Self, ScaffoldError>
+    where
+        C: PinnableCircuit<Fr>,
+    {
+        let pk = gen_pk(&params, circuit, None);
+        // `gen_pk` synthesized the circuit, so its break points are known
+        let pinning = C::Pinning::from_var(circuit.break_points());
+        let mut pinning =
+            serde_json::to_value(pinning).map_err(|e| invalid_pinning(e.to_string()))?;
+        let fingerprint = CircuitFingerprint::new(circuit)?;
+        set_pinning_field(&mut pinning, FINGERPRINT_FIELD, &fingerprint)
+            .and_then(|_| set_pinning_field(&mut pinning, MULTIOPEN_FIELD, &multiopen))
+            .map_err(invalid_pinning)?;
+        let vk_digest = vk_digest(pk.get_vk());
+        Ok(Self {
+            name: name.to_string(),
+            params,
+            pk,
+            pinning,
+            multiopen,
+            vk_digest,
+            check_shape: false,
+            _precircuit: PhantomData,
+        })
+    }
+
+    /// Creates a prover from the results of an earlier keygen, e.g. [`Self::pk`] and [`Self::pinning`] of another prover.
+    pub fn new(
+        name: &str,
+        params: ParamsKZG<Bn256>,
+        pk: ProvingKey<G1Affine>,
+        pinning: Value,
+    ) -> Result<Self, ScaffoldError> {
+        serde_json::from_value::<P::Pinning>(pinning.clone())
+            .map_err(|e| invalid_pinning(e.to_string()))?;
+        let multiopen = pinning_field(&pinning, MULTIOPEN_FIELD)
+            .map_err(invalid_pinning)?
+            .unwrap_or(MultiOpenKind::Shplonk);
+        let vk_digest = vk_digest(pk.get_vk());
+        Ok(Self {
+            name: name.to_string(),
+            params,
+            pk,
+            pinning,
+            multiopen,
+            vk_digest,
+            check_shape: false,
+            _precircuit: PhantomData,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn params(&self) -> &ParamsKZG<Bn256> {
+        &self.params
+    }
+
+    pub fn pk(&self) -> &ProvingKey<G1Affine> {
+        &self.pk
+    }
+
+    /// The pinning, as written to `configs/<name>.json` by the `keygen` command
+    pub fn pinning(&self) -> &Value {
+        &self.pinning
+    }
+
+    pub fn multiopen(&self) -> MultiOpenKind {
+        self.multiopen
+    }
+
+    /// Makes each proof first check its input against the circuit shape of keygen, see [`Self::check_input_shape`].
+    ///
+    /// Off by default, since the check synthesizes the circuit with the mock prover on top of proving it. Turn it on
+    /// when inputs can have a different shape than the keys, e.g. when they come from the command line.
+    pub fn with_shape_check(mut self, check_shape: bool) -> Self {
+        self.check_shape = check_shape;
+        self
+    }
+
+    /// Proves the circuit of `precircuit` with `transcript` and the pinned multiopen scheme.
+    ///
+    /// With [`Self::with_shape_check`], returns an error without proving if the circuit has a different shape than the
+    /// one of keygen. Otherwise such an input fails while proving, or yields a proof that does not verify.
+    pub fn prove(
+        &self,
+        precircuit: P,
+        transcript: TranscriptKind,
+    ) -> Result<ProofBundle, ScaffoldError> {
+        self.prove_with(precircuit, transcript, None, &mut ProveProgress::none())
+    }
+
+    /// Same as [`Self::prove`], reporting each [`ProofStage`] to `progress`.
+    ///
+    /// Returns [`ScaffoldError::Cancelled`] as soon as the token of `progress` is cancelled, see [`ProveProgress`].
+    pub fn prove_with_progress(
+        &self,
+        precircuit: P,
+        transcript: TranscriptKind,
+        progress: &mut ProveProgress<'_>,
+    ) -> Result<ProofBundle, ScaffoldError> {
+        self.prove_with(precircuit, transcript, None, progress)
+    }
+
+    /// Same as [`Self::prove_with_progress`], but if the proof can be aggregated, it is also written as a snark to `snark_path`.
+    pub fn prove_and_write_snark(
+        &self,
+        precircuit: P,
+        transcript: TranscriptKind,
+        snark_path: &Path,
+        progress: &mut ProveProgress<'_>,
+    ) -> Result<ProofBundle, ScaffoldError> {
+        self.prove_with(precircuit, transcript, Some(snark_path), progress)
+    }
+
+    fn prove_with(
+        &self,
+        precircuit: P,
+        transcript: TranscriptKind,
+        snark_path: Option<&Path>,
+        progress: &mut ProveProgress<'_>,
+    ) -> Result<ProofBundle, ScaffoldError> {
+        progress.enter(ProofStage::Witness)?;
+        if self.check_shape {
+            self.check_input_shape(precircuit.clone())?;
+            progress.check()?;
+        }
+        let pinning = self.read_pinning()?;
+        pinning.set_var();
+        let circuit =
+            precircuit.create_circuit(CircuitBuilderStage::Prover, Some(pinning), &self.params);
+        check_instance_names()?;
+        let (params, pk) = (&self.params, &self.pk);
+        let multiopen = self.multiopen;
+        prove_bundle(params, pk, circuit, &self.name, transcript, multiopen, snark_path, progress)
+    }
+
+    /// Returns an error if the circuit of `precircuit` has a different shape than the one fingerprinted at keygen.
+    ///
+    /// This synthesizes the circuit with the mock prover, see [`CircuitFingerprint::synthesize`], but catches an input of
+    /// another shape before proving.
+    /// Pinnings written before fingerprints were recorded are not checked.
+    pub fn check_input_shape(&self, precircuit: P) -> Result<(), ScaffoldError> {
+        let Some(pinned) = pinning_field::<CircuitFingerprint>(&self.pinning, FINGERPRINT_FIELD)
+            .map_err(invalid_pinning)?
+        else {
+            return Ok(());
+        };
+        let pinning = self.read_pinning()?;
+        pinning.set_var();
+        // the pinning is only used for the lookup bits and minimum rows; keygen recomputes the break points
+        let circuit =
+            precircuit.create_circuit(CircuitBuilderStage::Keygen, Some(pinning), &self.params);
+        CircuitFingerprint::synthesize(self.params.k(), &circuit)?.check(&pinned)
+    }
+
+    /// Verifies a proof of this prover, as [`Verifier::verify`] would.
+    pub fn verify(&self, bundle: &ProofBundle) -> Result<(), ScaffoldError> {
+        let (params, vk) = (&self.params, self.pk.get_vk());
+        verify_bundle(params, vk, &self.vk_digest, &self.name, self.multiopen, bundle)
+    }
+
+    /// A verifier for the proofs of this prover, reusing its params and verifying key.
+    pub fn into_verifier(self) -> Verifier {
+        Verifier::new(&self.name, self.params, self.pk.get_vk().clone(), self.multiopen)
+    }
+
+    fn read_pinning(&self) -> Result<P::Pinning, ScaffoldError> {
+        serde_json::from_value(self.pinning.clone()).map_err(|e| invalid_pinning(e.to_string()))
+    }
+}
+
+fn invalid_pinning(reason: String) -> ScaffoldError {
+    ScaffoldError::Config(format!("Invalid pinning: {reason}"))
+}
+
+/// Proves `circuit` with `transcript` and `multiopen` and returns the proof bundle for the circuit `name`.
+///
+/// Proofs that can be aggregated are also written as a snark to `snark_path`, if given.
+/// The snark is the one `gen_snark_shplonk` would write, but proving goes through [`gen_proof`] to report `progress`.
+#[allow(clippy::too_many_arguments)]
+fn prove_bundle<C: CircuitExt<Fr>>(
+    params: &ParamsKZG<Bn256>,
+    pk: &ProvingKey<G1Affine>,
+    circuit: C,
+    name: &str,
+    transcript: TranscriptKind,
+    multiopen: MultiOpenKind,
+    snark_path: Option<&Path>,
+    progress: &mut ProveProgress<'_>,
+) -> Result<ProofBundle, ScaffoldError> {
+    let instances = circuit.instances();
+    let num_instance = circuit.num_instance();
+    let proof = gen_proof(params, pk, circuit, &instances, transcript, multiopen, progress)?;
+    if let Some(snark_path) = snark_path.filter(|_| aggregatable(transcript, multiopen)) {
+        let config = Config::kzg()
+            .with_num_instance(num_instance)
+            .with_accumulator_indices(C::accumulator_indices());
+        let protocol = compile(params, pk.get_vk(), config);
+        write_snark(snark_path, &Snark::new(protocol, instances.clone(), proof.clone()))?;
+    }
+    let vk = pk.get_vk();
+    Ok(ProofBundle::new(name, params.k(), vk, transcript, multiopen, instances, proof))
+}
+
+/// Writes `snark` to `path` in the format read by `read_snark`.
+fn write_snark(path: &Path, snark: &Snark) -> Result<(), ScaffoldError> {
+    let f = File::create(path).map_err(ScaffoldError::io(path))?;
+    bincode::serialize_into(f, snark)
+        .map_err(|e| ScaffoldError::io(path)(io::Error::new(io::ErrorKind::Other, e)))
+}
@@ -0,0 +1,294 @@
+//! Conversion of Powers-of-Tau ceremony files (`.ptau`, as written by snarkjs) into halo2 KZG params.
+//!
+//! A `.ptau` file is a list of sections, each starting with a `u32` id and a `u64` byte length.
+//! Section 1 is the header, section 2 holds the powers `[tau^i]_1` and section 3 the powers `[tau^i]_2`.
+//! Field elements are stored little-endian in Montgomery form, which is also how halo2curves serializes points
+//! in [`SerdeFormat::RawBytes`], so points can be read directly with `from_raw_bytes`.
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use halo2_base::halo2_proofs::{
+    arithmetic::{best_multiexp, g_to_lagrange},
+    halo2curves::{
+        bn256::{Bn256, Fr, G1Affine, G2Affine},
+        group::{ff::Field, prime::PrimeCurveAffine, Curve},
+        pairing::Engine,
+        serde::SerdeObject,
+    },
+    poly::{commitment::Params, kzg::commitment::ParamsKZG},
+    SerdeFormat,
+};
+use rand::rngs::OsRng;
+
+use super::error::ScaffoldError;
+
+const PTAU_MAGIC: &[u8; 4] = b"ptau";
+const HEADER_SECTION: u32 = 1;
+const TAU_G1_SECTION: u32 = 2;
+const TAU_G2_SECTION: u32 = 3;
+/// Number of bytes of an element of the BN254 base field
+const N8: usize = 32;
+/// The BN254 base field modulus, big-endian hex
+const BN254_MODULUS: &str = "30644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd47";
+
+/// Reads the powers of tau of a `.ptau` file as KZG params of degree `k`.
+///
+/// Before returning, checks with pairings that the G1 powers are successive powers of the same `tau` as the G2 point.
+pub fn read_ptau(path: impl AsRef<Path>, k: u32) -> Result<ParamsKZG<Bn256>, ScaffoldError> {
+    let path = path.as_ref();
+    let invalid = |reason: String| ScaffoldError::Parse { path: path.to_path_buf(), reason };
+    let f = File::open(path).map_err(ScaffoldError::io(path))?;
+    let mut reader = BufReader::new(f);
+    let sections = read_sections(&mut reader)
+        .map_err(ScaffoldError::io(path))?
+        .ok_or_else(|| invalid("not a .ptau file".to_string()))?;
+    let section = |id: u32| {
+        sections.get(&id).copied().ok_or_else(|| invalid(format!("missing section {id}")))
+    };
+
+    // header: field element size, field modulus, power of the ceremony
+    let (offset, _) = section(HEADER_SECTION)?;
+    reader.seek(SeekFrom::Start(offset)).map_err(ScaffoldError::io(path))?;
+    let n8 = read_u32(&mut reader).map_err(ScaffoldError::io(path))? as usize;
+    let mut modulus = vec![0u8; n8];
+    reader.read_exact(&mut modulus).map_err(ScaffoldError::io(path))?;
+    modulus.reverse();
+    if n8 != N8 || hex::encode(&modulus) != BN254_MODULUS {
+        return Err(invalid("the ceremony is not over the BN254 curve".to_string()));
+    }
+    let power = read_u32(&mut reader).map_err(ScaffoldError::io(path))?;
+    if k == 0 || k > power {
+        return Err(ScaffoldError::Config(format!(
+            "ceremony at {path:?} supports circuits of degree 1 to {power}, but the circuit has degree {k}"
+        )));
+    }
+
+    let n = 1usize << k;
+    let (offset, size) = section(TAU_G1_SECTION)?;
+    if size < (n * 2 * N8) as u64 {
+        return Err(invalid(format!("section {TAU_G1_SECTION} has fewer than {n} points")));
+    }
+    reader.seek(SeekFrom::Start(offset)).map_err(ScaffoldError::io(path))?;
+    let g = (0..n)
+        .map(|i| {
+            read_point::<G1Affine>(&mut reader, 2 * N8)
+                .map_err(ScaffoldError::io(path))?
+                .ok_or_else(|| invalid(format!("[tau^{i}]_1 is not a point on the curve")))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (offset, size) = section(TAU_G2_SECTION)?;
+    if size < (2 * 4 * N8) as u64 {
+        return Err(invalid(format!("section {TAU_G2_SECTION} has fewer than 2 points")));
+    }
+    reader.seek(SeekFrom::Start(offset)).map_err(ScaffoldError::io(path))?;
+    let [g2, s_g2] = [0, 1].map(|i| {
+        read_point::<G2Affine>(&mut reader, 4 * N8)
+            .map_err(ScaffoldError::io(path))?
+            .ok_or_else(|| invalid(format!("[tau^{i}]_2 is not a point on the curve")))
+    });
+    let (g2, s_g2) = (g2?, s_g2?);
+
+    check_powers(&g, g2, s_g2).map_err(invalid)?;
+
+    let g_lagrange = g_to_lagrange(g.iter().map(|p| p.to_curve()).collect(), k);
+    // assemble the params in the raw format read by `ParamsKZG::read_custom`
+    let mut bytes = Vec::with_capacity(4 + 2 * n * 2 * N8 + 2 * 4 * N8);
+    bytes.extend_from_slice(&k.to_le_bytes());
+    for point in g.iter().chain(g_lagrange.iter()) {
+        bytes.extend(point.to_raw_bytes());
+    }
+    bytes.extend(g2.to_raw_bytes());
+    bytes.extend(s_g2.to_raw_bytes());
+    ParamsKZG::<Bn256>::read_custom(&mut &bytes[..], SerdeFormat::RawBytes)
+        .map_err(|e| invalid(format!("{e:?}")))
+}
+
+/// Converts the `.ptau` file at `ptau_path` into KZG params of degree `k`, written to `output_path` in the format read by `--srs`.
+pub fn convert_ptau(
+    ptau_path: impl AsRef<Path>,
+    k: u32,
+    output_path: impl AsRef<Path>,
+) -> Result<(), ScaffoldError> {
+    let params = read_ptau(ptau_path, k)?;
+    let output_path = output_path.as_ref();
+    let f = File::create(output_path).map_err(ScaffoldError::io(output_path))?;
+    params.write(&mut BufWriter::new(f)).map_err(ScaffoldError::io(output_path))
+}
+
+/// Checks that `g[0]` and `g2` are the generators, and that `g[i] = [tau^i]_1` where `s_g2 = [tau]_2`.
+///
+/// Instead of one pairing per power, a random linear combination `sum_i r_i * (g[i + 1], g[i])` is checked against `(g2, s_g2)`.
+fn check_powers(g: &[G1Affine], g2: G2Affine, s_g2: G2Affine) -> Result<(), String> {
+    if g[0] != G1Affine::generator() || g2 != G2Affine::generator() {
+        return Err("the ceremony does not start from the standard generators".to_string());
+    }
+    if Bn256::pairing(&g[1], &g2) != Bn256::pairing(&g[0], &s_g2) {
+        return Err("[tau]_1 and [tau]_2 use different values of tau".to_string());
+    }
+    let coeffs = (1..g.len()).map(|_| Fr::random(OsRng)).collect::<Vec<_>>();
+    let lhs = best_multiexp(&coeffs, &g[1..]).to_affine();
+    let rhs = best_multiexp(&coeffs, &g[..g.len() - 1]).to_affine();
+    if Bn256::pairing(&lhs, &g2) != Bn256::pairing(&rhs, &s_g2) {
+        return Err("the G1 points are not successive powers of tau".to_string());
+    }
+    Ok(())
+}
+
+/// Reads the section table of a `.ptau` file, mapping each section id to the offset and length of its data.
+///
+/// Returns `None` if the file does not start with the `.ptau` magic bytes.
+fn read_sections(reader: &mut (impl Read + Seek)) -> io::Result<Option<HashMap<u32, (u64, u64)>>> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != PTAU_MAGIC {
+        return Ok(None);
+    }
+    let _version = read_u32(reader)?;
+    let num_sections = read_u32(reader)?;
+    let mut sections = HashMap::new();
+    for _ in 0..num_sections {
+        let id = read_u32(reader)?;
+        let size = read_u64(reader)?;
+        let offset = reader.stream_position()?;
+        sections.insert(id, (offset, size));
+        reader.seek(SeekFrom::Current(size as i64))?;
+    }
+    Ok(Some(sections))
+}
+
+fn read_point<C: SerdeObject>(reader: &mut impl Read, len: usize) -> io::Result<Option<C>> {
+    let mut bytes = vec![0u8; len];
+    reader.read_exact(&mut bytes)?;
+    Ok(C::from_raw_bytes(&bytes))
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, fs, io::Cursor, process};
+
+    use halo2_base::halo2_proofs::{
+        halo2curves::{
+            bn256::{Fr, G1Affine, G2Affine},
+            group::{ff::Field, prime::PrimeCurveAffine},
+            serde::SerdeObject,
+        },
+        poly::commitment::Params,
+    };
+    use rand::rngs::OsRng;
+
+    use super::{
+        check_powers, read_ptau, read_sections, BN254_MODULUS, HEADER_SECTION, N8, PTAU_MAGIC,
+        TAU_G1_SECTION, TAU_G2_SECTION,
+    };
+    use crate::scaffold::error::ScaffoldError;
+
+    /// `[tau^i]_1` for `i < n`, `[1]_2` and `[tau]_2`
+    fn powers(tau: Fr, n: usize) -> (Vec<G1Affine>, G2Affine, G2Affine) {
+        let g =
+            (0..n as u64).map(|i| (G1Affine::generator() * tau.pow_vartime([i])).into()).collect();
+        (g, G2Affine::generator(), (G2Affine::generator() * tau).into())
+    }
+
+    /// A `.ptau` file with the given sections, in the layout written by snarkjs.
+    fn ptau_bytes(sections: &[(u32, Vec<u8>)]) -> Vec<u8> {
+        let mut bytes = PTAU_MAGIC.to_vec();
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend((sections.len() as u32).to_le_bytes());
+        for (id, data) in sections {
+            bytes.extend(id.to_le_bytes());
+            bytes.extend((data.len() as u64).to_le_bytes());
+            bytes.extend(data);
+        }
+        bytes
+    }
+
+    fn header_section(modulus: &str, power: u32) -> Vec<u8> {
+        let mut modulus = hex::decode(modulus).unwrap();
+        modulus.reverse();
+        let mut bytes = (N8 as u32).to_le_bytes().to_vec();
+        bytes.extend(modulus);
+        bytes.extend(power.to_le_bytes());
+        bytes
+    }
+
+    fn write_ptau(name: &str, header: Vec<u8>, tau: Fr, k: u32) -> std::path::PathBuf {
+        let (g, g2, s_g2) = powers(tau, 1 << k);
+        let g1_section = g.iter().flat_map(|p| p.to_raw_bytes()).collect();
+        let g2_section = [g2, s_g2].iter().flat_map(|p| p.to_raw_bytes()).collect();
+        let bytes = ptau_bytes(&[
+            (HEADER_SECTION, header),
+            (TAU_G1_SECTION, g1_section),
+            (TAU_G2_SECTION, g2_section),
+        ]);
+        let path = env::temp_dir().join(format!("halo2-scaffold-{}-{name}.ptau", process::id()));
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_read_sections() {
+        let bytes = ptau_bytes(&[(1, vec![0; 5]), (3, vec![0; 2])]);
+        let sections = read_sections(&mut Cursor::new(bytes)).unwrap().unwrap();
+        // magic, version and number of sections, then a u32 id and u64 length before each section
+        assert_eq!(sections[&1], (12 + 12, 5));
+        assert_eq!(sections[&3], (12 + 12 + 5 + 12, 2));
+        assert!(!sections.contains_key(&2));
+
+        let mut bytes = ptau_bytes(&[]);
+        bytes[..4].copy_from_slice(b"zkey");
+        assert!(read_sections(&mut Cursor::new(bytes)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_check_powers() {
+        let tau = Fr::random(OsRng);
+        let (g, g2, s_g2) = powers(tau, 8);
+        check_powers(&g, g2, s_g2).unwrap();
+
+        let (_, _, other_s_g2) = powers(tau.double(), 8);
+        assert!(check_powers(&g, g2, other_s_g2).is_err());
+
+        let mut swapped = g.clone();
+        swapped.swap(3, 4);
+        assert!(check_powers(&swapped, g2, s_g2).is_err());
+
+        let scaled = g.iter().map(|p| (*p * Fr::from(2)).into()).collect::<Vec<G1Affine>>();
+        assert!(check_powers(&scaled, g2, s_g2).is_err());
+    }
+
+    #[test]
+    fn test_read_ptau() {
+        let path = write_ptau("valid", header_section(BN254_MODULUS, 3), Fr::random(OsRng), 3);
+        let params = read_ptau(&path, 2).unwrap();
+        assert_eq!(params.k(), 2);
+        assert!(matches!(read_ptau(&path, 4), Err(ScaffoldError::Config(_))));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_read_ptau_other_curve() {
+        // same size as the BN254 base field modulus, but a different prime
+        let fr_modulus = "30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001";
+        let path = write_ptau("other-curve", header_section(fr_modulus, 3), Fr::random(OsRng), 3);
+        let e = read_ptau(&path, 2).unwrap_err();
+        assert!(e.to_string().contains("not over the BN254 curve"), "{e}");
+        fs::remove_file(path).unwrap();
+    }
+}
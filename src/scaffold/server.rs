@@ -0,0 +1,606 @@
+//! A long-lived prover process, started with the `serve` command.
+//!
+//! Reading a large proving key dominates the latency of a single `prove`. `serve` reads the params and the proving keys
+//! of the circuits named by `--circuits` once, then answers [JSON-RPC 2.0](https://www.jsonrpc.org/specification) requests
+//! on a Unix socket, one request per line, or with `--http` on a localhost address, one request per `POST`.
+//! Requests from all connections go through one queue and are run one at a time, in the order they arrive,
+//! since proving already uses every core. The queue holds at most `--queue-size` requests; further requests are
+//! rejected until it drains. With `--request-timeout`, a request that has not been answered in time is cancelled,
+//! see [`CancellationToken`] for how soon a proof in progress stops.
+//! Request lines and HTTP bodies are limited to [`MAX_REQUEST_BYTES`], HTTP request heads to [`MAX_HTTP_HEAD_BYTES`],
+//! and at most [`MAX_CONNECTIONS`] connections are served at once.
+//!
+//! A prover panic only fails its request if the binary is built with `panic = "unwind"`, e.g. with `--profile local`.
+//! The `release` profile aborts on panic, which stops the whole server; `serve` warns about it at startup.
+//!
+//! Methods:
+//! - `prove` with params `{"circuit": NAME, "input": INPUT, "transcript": TRANSCRIPT}` proves `INPUT`, the JSON of an input file,
+//!   and returns a [`ProveResult`]. `transcript` is optional and defaults to `poseidon`.
+//! - `verify` with params `{"circuit": NAME, "bundle": HEX}` verifies the `bundle` returned by `prove` and returns `true`.
+//! - `circuits` returns the names of the circuits being served.
+//!
+//! Errors of the scaffold itself have the [`ScaffoldError::exit_code`] of the same error on the command line as their code.
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    os::unix::{
+        fs::FileTypeExt,
+        net::{UnixListener, UnixStream},
+    },
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, RecvTimeoutError, SyncSender, TrySendError},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use axiom_eth::util::{circuit::PreCircuit, Halo2ConfigPinning};
+use halo2_base::{
+    halo2_proofs::{halo2curves::bn256::Bn256, poly::kzg::commitment::ParamsKZG},
+    utils::fe_to_biguint,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::{
+    bundle::ProofBundle,
+    cmd::{Cli, Degree, SnarkCmd},
+    error::ScaffoldError,
+    multiopen::{pinned_multiopen, MultiOpenKind},
+    pinning::{read_pinning, ScaffoldPinning},
+    progress::{CancellationToken, ProveProgress},
+    prover::Prover,
+    read_prover,
+    srs::load_srs,
+    transcript::TranscriptKind,
+};
+
+/// JSON-RPC error codes defined by the specification
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+/// Implementation-defined server error: the queue is full
+const SERVER_BUSY: i64 = -32000;
+
+/// Maximum size in bytes of one request, i.e. of a line on the Unix socket or of an HTTP body
+pub const MAX_REQUEST_BYTES: usize = 16 << 20;
+/// Maximum size in bytes of the request line and headers of an HTTP request
+pub const MAX_HTTP_HEAD_BYTES: usize = 16 << 10;
+/// Maximum number of connections served at once, each on its own thread. Further connections are refused.
+pub const MAX_CONNECTIONS: usize = 64;
+/// How long an HTTP client may stay silent while sending its request
+const HTTP_READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RpcRequest {
+    #[serde(default)]
+    pub jsonrpc: String,
+    #[serde(default)]
+    pub id: Value,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RpcResponse {
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+}
+
+impl From<ScaffoldError> for RpcError {
+    fn from(e: ScaffoldError) -> Self {
+        Self::new(e.exit_code() as i64, e.to_string())
+    }
+}
+
+impl RpcResponse {
+    fn new(id: Value, result: Result<Value, RpcError>) -> Self {
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        Self { jsonrpc: "2.0".to_string(), id, result, error }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct ProveParams {
+    circuit: String,
+    input: Value,
+    #[serde(default)]
+    transcript: Option<TranscriptKind>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct VerifyParams {
+    circuit: String,
+    bundle: String,
+}
+
+/// The result of `prove`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProveResult {
+    /// Public instances per instance column, as decimal strings
+    pub instances: Vec<Vec<String>>,
+    /// The proof bytes, hex encoded
+    pub proof: String,
+    pub transcript: TranscriptKind,
+    pub multiopen: MultiOpenKind,
+    /// The proof bundle, hex encoded, as written to `data/<name>.bundle` by `prove`. Pass it to `verify`.
+    pub bundle: String,
+}
+
+impl ProveResult {
+    fn new(bundle: &ProofBundle) -> Self {
+        Self {
+            instances: bundle
+                .instances
+                .iter()
+                .map(|column| column.iter().map(|x| fe_to_biguint(x).to_string()).collect())
+                .collect(),
+            proof: hex::encode(&bundle.proof),
+            transcript: bundle.header.transcript,
+            multiopen: bundle.header.multiopen,
+            bundle: hex::encode(bundle.to_bytes()),
+        }
+    }
+}
+
+/// A request waiting in the queue, with the channel its response is sent to
+struct Job {
+    request: RpcRequest,
+    reply: mpsc::Sender<RpcResponse>,
+    /// Cancelled once the request times out
+    cancellation: CancellationToken,
+}
+
+/// The sending end of the job queue, shared by all connections
+#[derive(Clone)]
+struct Queue {
+    jobs: SyncSender<Job>,
+    timeout: Option<Duration>,
+}
+
+/// Runs `serve`: loads the proving key of each circuit in `--circuits` and answers requests until the process is killed.
+///
+/// `precircuit` creates the circuit for one input; `keygen` must have been run for every circuit.
+/// The proving keys are read for the circuit of `placeholder`, which can be any input, whatever its shape.
+pub fn serve<T, P>(
+    precircuit: impl Fn(T) -> P,
+    placeholder: T,
+    cli: Cli,
+) -> Result<(), ScaffoldError>
+where
+    T: DeserializeOwned + Clone,
+    P: PreCircuit + Clone,
+    P::Pinning: ScaffoldPinning,
+{
+    if !matches!(cli.command, SnarkCmd::Serve) {
+        return Err(ScaffoldError::Config(format!("{} does not run a server", cli.command)));
+    }
+    if cli.queue_size == 0 {
+        return Err(ScaffoldError::Config("--queue-size must be at least 1".to_string()));
+    }
+    if !cfg!(panic = "unwind") {
+        eprintln!(
+            "Warning: built with `panic = \"abort\"`, so a prover panic stops the server; build with `--profile local` to only fail the request"
+        );
+    }
+    let provers = load_provers(&precircuit, placeholder, &cli)?;
+
+    let (jobs, queue) = mpsc::sync_channel::<Job>(cli.queue_size);
+    let jobs = Queue { jobs, timeout: cli.request_timeout.map(Duration::from_secs) };
+    match cli.http {
+        Some(addr) => {
+            if !addr.ip().is_loopback() {
+                return Err(ScaffoldError::Config(format!(
+                    "--http {addr} is not a localhost address; the server has no authentication"
+                )));
+            }
+            let listener = TcpListener::bind(addr).map_err(|e| bind_error(&addr, e))?;
+            thread::spawn(move || accept_http(listener, jobs));
+            println!("Serving {} on http://{addr}", names(&provers));
+        }
+        None => {
+            let data_path = cli.data_path.clone().unwrap_or_else(|| PathBuf::from("data"));
+            let socket_path = cli
+                .socket
+                .clone()
+                .unwrap_or_else(|| data_path.join(PathBuf::from(format!("{}.sock", cli.name))));
+            let listener = bind_unix(&socket_path)?;
+            thread::spawn(move || accept_unix(listener, jobs));
+            println!("Serving {} on {socket_path:?}", names(&provers));
+        }
+    }
+
+    // jobs run one at a time on this thread, which owns the provers
+    for Job { request, reply, cancellation } in queue {
+        // the request timed out while it was queued, and its client already has an error
+        if cancellation.is_cancelled() {
+            continue;
+        }
+        let id = request.id.clone();
+        // a panic inside halo2 only fails this request, not the server, unless panics abort, see the module docs
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            handle(&provers, &precircuit, request, cancellation)
+        }))
+        .unwrap_or_else(|_| Err(RpcError::new(INTERNAL_ERROR, "prover panicked")));
+        // the client may have disconnected in the meantime
+        let _ = reply.send(RpcResponse::new(id, result));
+    }
+    Ok(())
+}
+
+/// Reads the params and proving key of each circuit to serve. Params are read once per degree.
+fn load_provers<T, P>(
+    precircuit: &impl Fn(T) -> P,
+    placeholder: T,
+    cli: &Cli,
+) -> Result<HashMap<String, Prover<P>>, ScaffoldError>
+where
+    T: Clone,
+    P: PreCircuit + Clone,
+    P::Pinning: ScaffoldPinning,
+{
+    let config_path = cli.config_path.clone().unwrap_or_else(|| PathBuf::from("configs"));
+    let data_path = cli.data_path.clone().unwrap_or_else(|| PathBuf::from("data"));
+    let circuits = match cli.circuits.is_empty() {
+        true => vec![cli.name.clone()],
+        false => cli.circuits.clone(),
+    };
+
+    let mut srs: HashMap<u32, ParamsKZG<Bn256>> = HashMap::new();
+    let mut provers = HashMap::new();
+    for name in circuits {
+        let pinning_path = config_path.join(PathBuf::from(format!("{name}.json")));
+        let pinning: P::Pinning = read_pinning(&pinning_path)?;
+        pinning.check_matches(cli.lookup_bits, cli.minimum_rows)?;
+        pinned_multiopen(&pinning_path, cli.multiopen)?;
+        let k = match cli.degree {
+            Degree::Fixed(k) => k,
+            Degree::Auto => pinning.degree(),
+        };
+        pinning.check_degree(k)?;
+        if !srs.contains_key(&k) {
+            srs.insert(k, load_srs(cli, k)?);
+        }
+        let params = srs[&k].clone();
+        let pk_path = data_path.join(PathBuf::from(format!("{name}.pk")));
+        let placeholder = precircuit(placeholder.clone());
+        let prover = read_prover(&name, params, placeholder, &pinning_path, &pk_path)?
+            .with_shape_check(cli.check_shape);
+        println!("Proving key for {name} (k = {k}) read from: {pk_path:?}");
+        provers.insert(name, prover);
+    }
+    Ok(provers)
+}
+
+fn handle<T, P>(
+    provers: &HashMap<String, Prover<P>>,
+    precircuit: &impl Fn(T) -> P,
+    request: RpcRequest,
+    cancellation: CancellationToken,
+) -> Result<Value, RpcError>
+where
+    T: DeserializeOwned,
+    P: PreCircuit + Clone,
+{
+    if request.jsonrpc != "2.0" {
+        return Err(RpcError::new(INVALID_REQUEST, "only JSON-RPC 2.0 is supported"));
+    }
+    let prover = |name: &str| {
+        provers
+            .get(name)
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("circuit {name:?} is not served")))
+    };
+    match request.method.as_str() {
+        "prove" => {
+            let params: ProveParams = parse_params(request.params)?;
+            let prover = prover(&params.circuit)?;
+            let input = serde_json::from_value(params.input)
+                .map_err(|e| RpcError::new(INVALID_PARAMS, format!("invalid input: {e}")))?;
+            let transcript = params.transcript.unwrap_or_default();
+            let mut progress = ProveProgress::new(|_| {}, cancellation);
+            let bundle =
+                prover.prove_with_progress(precircuit(input), transcript, &mut progress)?;
+            Ok(serde_json::to_value(ProveResult::new(&bundle)).unwrap())
+        }
+        "verify" => {
+            let params: VerifyParams = parse_params(request.params)?;
+            let prover = prover(&params.circuit)?;
+            let bundle = hex::decode(params.bundle.trim_start_matches("0x"))
+                .map_err(|e| e.to_string())
+                .and_then(|bytes| ProofBundle::from_bytes(&bytes))
+                .map_err(|e| RpcError::new(INVALID_PARAMS, format!("invalid bundle: {e}")))?;
+            prover.verify(&bundle)?;
+            Ok(json!(true))
+        }
+        "circuits" => Ok(json!(sorted_names(provers))),
+        method => Err(RpcError::new(METHOD_NOT_FOUND, format!("method {method:?} not found"))),
+    }
+}
+
+fn parse_params<V: DeserializeOwned>(params: Value) -> Result<V, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn sorted_names<P: PreCircuit>(provers: &HashMap<String, Prover<P>>) -> Vec<&str> {
+    let mut names = provers.keys().map(|name| name.as_str()).collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+fn names<P: PreCircuit>(provers: &HashMap<String, Prover<P>>) -> String {
+    sorted_names(provers).join(", ")
+}
+
+/// Queues the request in `body` and waits for its response, cancelling it if it times out.
+fn submit(queue: &Queue, body: &[u8]) -> RpcResponse {
+    let request: RpcRequest = match serde_json::from_slice(body) {
+        Ok(request) => request,
+        Err(e) => {
+            return RpcResponse::new(Value::Null, Err(RpcError::new(PARSE_ERROR, e.to_string())))
+        }
+    };
+    let id = request.id.clone();
+    let (reply, response) = mpsc::channel();
+    let cancellation = CancellationToken::new();
+    let job = Job { request, reply, cancellation: cancellation.clone() };
+    match queue.jobs.try_send(job) {
+        Ok(()) => {}
+        Err(TrySendError::Full(_)) => {
+            let error = RpcError::new(SERVER_BUSY, "the request queue is full, try again later");
+            return RpcResponse::new(id, Err(error));
+        }
+        Err(TrySendError::Disconnected(_)) => {
+            return RpcResponse::new(id, Err(RpcError::new(INTERNAL_ERROR, "server is stopping")));
+        }
+    }
+    let dropped = || RpcError::new(INTERNAL_ERROR, "request was dropped");
+    let response = match queue.timeout {
+        Some(timeout) => response.recv_timeout(timeout).map_err(|e| match e {
+            RecvTimeoutError::Timeout => {
+                cancellation.cancel();
+                let cancelled = ScaffoldError::Cancelled;
+                let message = format!("{cancelled}: no response within {timeout:?}");
+                RpcError::new(cancelled.exit_code() as i64, message)
+            }
+            RecvTimeoutError::Disconnected => dropped(),
+        }),
+        None => response.recv().map_err(|_| dropped()),
+    };
+    response.unwrap_or_else(|error| RpcResponse::new(id, Err(error)))
+}
+
+fn bind_error(addr: &dyn std::fmt::Debug, e: io::Error) -> ScaffoldError {
+    ScaffoldError::Config(format!("Failed to listen on {addr:?}: {e}"))
+}
+
+/// Binds the Unix socket at `path`, replacing a stale socket file left behind by a server that was killed.
+///
+/// Anything at `path` that is not a socket is left alone and reported as a configuration error.
+fn bind_unix(path: &Path) -> Result<UnixListener, ScaffoldError> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) => {
+            if !metadata.file_type().is_socket() {
+                return Err(ScaffoldError::Config(format!(
+                    "--socket {path:?} exists and is not a socket; remove it or pick another path"
+                )));
+            }
+            if UnixStream::connect(path).is_ok() {
+                return Err(ScaffoldError::Config(format!(
+                    "Another server is listening on {path:?}"
+                )));
+            }
+            fs::remove_file(path).map_err(ScaffoldError::io(path))?;
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(ScaffoldError::io(path)(e)),
+    }
+    UnixListener::bind(path).map_err(|e| bind_error(&path, e))
+}
+
+/// One of the [`MAX_CONNECTIONS`] connection slots, freed when dropped
+struct Connection(Arc<AtomicUsize>);
+
+impl Connection {
+    /// Takes a slot from the `open` connections, or returns `None` if all are taken.
+    fn open(open: &Arc<AtomicUsize>) -> Option<Self> {
+        if open.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            open.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+        Some(Self(open.clone()))
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn too_many_connections() -> String {
+    format!("the server already has {MAX_CONNECTIONS} connections, try again later")
+}
+
+fn accept_unix(listener: UnixListener, jobs: Queue) {
+    let open = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        match stream {
+            Ok(mut stream) => {
+                let Some(connection) = Connection::open(&open) else {
+                    let error = RpcError::new(SERVER_BUSY, too_many_connections());
+                    let response = RpcResponse::new(Value::Null, Err(error));
+                    let _ = writeln!(stream, "{}", serde_json::to_string(&response).unwrap());
+                    continue;
+                };
+                let jobs = jobs.clone();
+                thread::spawn(move || {
+                    let _connection = connection;
+                    if let Err(e) = serve_unix_connection(stream, &jobs) {
+                        eprintln!("Connection closed: {e}");
+                    }
+                });
+            }
+            Err(e) => eprintln!("Failed to accept connection: {e}"),
+        }
+    }
+}
+
+/// Answers each line of `stream` with one line holding the response.
+///
+/// A line longer than [`MAX_REQUEST_BYTES`] is answered with an error and closes the connection.
+fn serve_unix_connection(stream: UnixStream, jobs: &Queue) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    loop {
+        let mut line = vec![];
+        let limit = MAX_REQUEST_BYTES as u64 + 1;
+        if reader.by_ref().take(limit).read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+        if line.len() > MAX_REQUEST_BYTES {
+            let message = format!("request is longer than {MAX_REQUEST_BYTES} bytes");
+            let response =
+                RpcResponse::new(Value::Null, Err(RpcError::new(INVALID_REQUEST, message)));
+            return writeln!(writer, "{}", serde_json::to_string(&response)?);
+        }
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        let response = submit(jobs, &line);
+        writeln!(writer, "{}", serde_json::to_string(&response)?)?;
+    }
+}
+
+fn accept_http(listener: TcpListener, jobs: Queue) {
+    let open = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        match stream {
+            Ok(mut stream) => {
+                let Some(connection) = Connection::open(&open) else {
+                    let _ = write_http_error(
+                        &mut stream,
+                        "503 Service Unavailable",
+                        &too_many_connections(),
+                    );
+                    continue;
+                };
+                let jobs = jobs.clone();
+                thread::spawn(move || {
+                    let _connection = connection;
+                    if let Err(e) = serve_http_connection(stream, &jobs) {
+                        eprintln!("Connection closed: {e}");
+                    }
+                });
+            }
+            Err(e) => eprintln!("Failed to accept connection: {e}"),
+        }
+    }
+}
+
+/// Answers one HTTP `POST` whose body is a JSON-RPC request, then closes the connection.
+///
+/// The request line and headers may be at most [`MAX_HTTP_HEAD_BYTES`] long, and the body must have a valid
+/// `Content-Length` of at most [`MAX_REQUEST_BYTES`]. Each read times out after [`HTTP_READ_TIMEOUT`].
+fn serve_http_connection(stream: TcpStream, jobs: &Queue) -> io::Result<()> {
+    stream.set_read_timeout(Some(HTTP_READ_TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut head_left = MAX_HTTP_HEAD_BYTES;
+    let head_too_large = |writer: &mut TcpStream| {
+        let body = format!("request line and headers are longer than {MAX_HTTP_HEAD_BYTES} bytes");
+        write_http_error(writer, "431 Request Header Fields Too Large", &body)
+    };
+    let Some(request_line) = read_head_line(&mut reader, &mut head_left)? else {
+        return head_too_large(&mut writer);
+    };
+    let mut content_length = None;
+    loop {
+        let Some(header) = read_head_line(&mut reader, &mut head_left)? else {
+            return head_too_large(&mut writer);
+        };
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((key, value)) = header.split_once(':') {
+            if key.eq_ignore_ascii_case("content-length") {
+                content_length = Some(value.trim().parse::<usize>());
+            }
+        }
+    }
+    if !request_line.starts_with("POST ") {
+        let body = "only POST is supported";
+        return write!(
+            writer,
+            "HTTP/1.1 405 Method Not Allowed\r\nAllow: POST\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+    }
+    let content_length = match content_length {
+        Some(Ok(length)) if length <= MAX_REQUEST_BYTES => length,
+        Some(Ok(_)) => {
+            let body = format!("request body is larger than {MAX_REQUEST_BYTES} bytes");
+            return write_http_error(&mut writer, "413 Payload Too Large", &body);
+        }
+        Some(Err(_)) => {
+            return write_http_error(&mut writer, "400 Bad Request", "invalid Content-Length")
+        }
+        None => return write_http_error(&mut writer, "400 Bad Request", "missing Content-Length"),
+    };
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body)?;
+    let response = serde_json::to_string(&submit(jobs, &body))?;
+    write!(
+        writer,
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{response}",
+        response.len()
+    )
+}
+
+/// Reads one line of an HTTP request head, counting it against the `head_left` bytes the head may still take.
+/// Returns `None` if the line does not fit.
+fn read_head_line(reader: &mut impl BufRead, head_left: &mut usize) -> io::Result<Option<String>> {
+    let mut line = vec![];
+    reader.by_ref().take(*head_left as u64 + 1).read_until(b'\n', &mut line)?;
+    if line.len() > *head_left {
+        return Ok(None);
+    }
+    *head_left -= line.len();
+    Ok(Some(String::from_utf8_lossy(&line).into_owned()))
+}
+
+fn write_http_error(writer: &mut impl Write, status: &str, body: &str) -> io::Result<()> {
+    write!(
+        writer,
+        "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
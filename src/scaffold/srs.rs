@@ -0,0 +1,66 @@
+//! Loading the universal trusted setup (SRS) used for keygen, proving and verification.
+//!
+//! Proofs are only sound if nobody knows the toxic waste of the SRS, so production deployments must use the output of a
+//! ceremony, passed with `--srs`. A random setup from `gen_srs` is only created with `--unsafe-setup`, or for commands
+//! that never create or check a proof.
+use std::{fs::File, io::BufReader, path::Path};
+
+use halo2_base::{
+    halo2_proofs::{
+        halo2curves::bn256::Bn256,
+        poly::{commitment::Params, kzg::commitment::ParamsKZG},
+    },
+    utils::fs::gen_srs,
+};
+
+use super::{
+    cmd::{Cli, SnarkCmd},
+    error::ScaffoldError,
+    ptau::read_ptau,
+};
+
+/// Reads KZG params from `path` and downsizes them to degree `k` if they are larger.
+///
+/// The file must be in the raw format written by `ParamsKZG::write`, which is the format used by `gen_srs`,
+/// or a `.ptau` ceremony file, which is converted with [`read_ptau`].
+pub fn read_srs(path: impl AsRef<Path>, k: u32) -> Result<ParamsKZG<Bn256>, ScaffoldError> {
+    let path = path.as_ref();
+    if path.extension().map_or(false, |ext| ext == "ptau") {
+        return read_ptau(path, k);
+    }
+    let f = File::open(path).map_err(ScaffoldError::io(path))?;
+    let mut params =
+        ParamsKZG::<Bn256>::read(&mut BufReader::new(f)).map_err(ScaffoldError::parse(path))?;
+    if params.k() < k {
+        return Err(ScaffoldError::Config(format!(
+            "SRS at {path:?} only supports circuits up to degree {}, but the circuit has degree {k}",
+            params.k()
+        )));
+    }
+    if params.k() > k {
+        params.downsize(k);
+    }
+    Ok(params)
+}
+
+/// The SRS of degree `k` for `cli.command`: read from `--srs` if given, otherwise randomly generated.
+///
+/// Commands that create or verify proofs refuse to use a random setup unless `--unsafe-setup` is passed.
+pub fn load_srs(cli: &Cli, k: u32) -> Result<ParamsKZG<Bn256>, ScaffoldError> {
+    if let Some(srs_path) = &cli.srs_path {
+        let params = read_srs(srs_path, k)?;
+        println!("Using universal trusted setup from: {srs_path:?}");
+        return Ok(params);
+    }
+    // the mock prover never uses the SRS itself, only its degree
+    let needs_setup = !matches!(cli.command, SnarkCmd::Mock | SnarkCmd::Stats);
+    if needs_setup && !cli.unsafe_setup {
+        return Err(ScaffoldError::Config(format!(
+            "{} needs a trusted setup: pass --srs <PATH> with a ceremony SRS, or --unsafe-setup to generate a random one for testing",
+            cli.command
+        )));
+    }
+    let params = gen_srs(k);
+    println!("Universal trusted setup (unsafe!) available at: params/kzg_bn254_{k}.srs");
+    Ok(params)
+}
@@ -0,0 +1,161 @@
+//! Machine-readable report of the shape and estimated cost of a circuit, for tracking cost regressions in CI.
+use std::{cell::RefCell, collections::HashSet};
+
+use axiom_eth::util::{circuit::PinnableCircuit, Halo2ConfigPinning};
+use halo2_base::{
+    gates::builder::{FlexGateConfigParams, GateThreadBuilder},
+    halo2_proofs::{
+        arithmetic::Field,
+        halo2curves::bn256::Fr,
+        plonk::{Circuit, ConstraintSystem},
+    },
+    utils::{fe_to_biguint, ScalarField},
+};
+use serde::{Deserialize, Serialize};
+use snark_verifier_sdk::CircuitExt;
+
+thread_local! {
+    static BUILDER_STATS: RefCell<Option<BuilderStats>> = RefCell::new(None);
+}
+
+/// Statistics that are only known to the `GateThreadBuilder`, not to the final circuit.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BuilderStats {
+    pub advice_cells_per_phase: Vec<usize>,
+    pub lookup_cells_per_phase: Vec<usize>,
+    /// Number of distinct constants, each of which takes a fixed cell
+    pub constants: usize,
+    pub num_advice_per_phase: Vec<usize>,
+    pub num_lookup_advice_per_phase: Vec<usize>,
+    pub num_fixed: usize,
+}
+
+impl BuilderStats {
+    /// Counts cells the same way `GateThreadBuilder::config` does.
+    pub fn new<F: ScalarField>(
+        builder: &GateThreadBuilder<F>,
+        params: &FlexGateConfigParams,
+    ) -> Self {
+        let advice_cells_per_phase = builder
+            .threads
+            .iter()
+            .map(|threads| threads.iter().map(|ctx| ctx.advice.len()).sum())
+            .collect();
+        let lookup_cells_per_phase = builder
+            .threads
+            .iter()
+            .map(|threads| threads.iter().map(|ctx| ctx.cells_to_lookup.len()).sum())
+            .collect();
+        let constants = builder
+            .threads
+            .iter()
+            .flatten()
+            .flat_map(|ctx| ctx.constant_equality_constraints.iter().map(|(c, _)| fe_to_biguint(c)))
+            .collect::<HashSet<_>>()
+            .len();
+        Self {
+            advice_cells_per_phase,
+            lookup_cells_per_phase,
+            constants,
+            num_advice_per_phase: params.num_advice_per_phase.clone(),
+            num_lookup_advice_per_phase: params.num_lookup_advice_per_phase.clone(),
+            num_fixed: params.num_fixed,
+        }
+    }
+
+    /// Records these stats for the circuit being built on the current thread, to be picked up by [`CircuitStats::new`].
+    pub fn set_current(self) {
+        BUILDER_STATS.with(|stats| *stats.borrow_mut() = Some(self));
+    }
+
+    fn take_current() -> Option<Self> {
+        BUILDER_STATS.with(|stats| stats.borrow_mut().take())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CircuitStats {
+    pub name: String,
+    pub degree: u32,
+    /// Only available for circuits built with `run` or `run_builder`
+    pub builder: Option<BuilderStats>,
+    /// Total number of advice columns, including lookup advice columns
+    pub num_advice_columns: usize,
+    /// Total number of fixed columns, including those for constants and the lookup table
+    pub num_fixed_columns: usize,
+    pub num_selectors: usize,
+    pub num_lookup_arguments: usize,
+    pub num_instances: Vec<usize>,
+    pub break_points: serde_json::Value,
+    /// Estimated size in bytes of a SHPLONK proof, see [`estimated_proof_size`]
+    pub estimated_proof_size: usize,
+}
+
+impl CircuitStats {
+    /// `circuit` should have been synthesized (e.g., by `MockProver`) so that its break points are known.
+    pub fn new<C: PinnableCircuit<Fr>>(name: &str, degree: u32, circuit: &C) -> Self {
+        let cs = constraint_system(circuit);
+        let pinning = C::Pinning::from_var(circuit.break_points());
+        let break_points = serde_json::to_value(pinning)
+            .expect("pinning should serialize to JSON")
+            .get("break_points")
+            .cloned()
+            .unwrap_or_default();
+        Self {
+            name: name.to_string(),
+            degree,
+            builder: BuilderStats::take_current(),
+            num_advice_columns: cs.num_advice_columns(),
+            num_fixed_columns: cs.num_fixed_columns(),
+            num_selectors: cs.num_selectors(),
+            num_lookup_arguments: cs.lookups().len(),
+            num_instances: circuit.num_instance(),
+            break_points,
+            estimated_proof_size: estimated_proof_size(&cs),
+        }
+    }
+}
+
+/// Number of product commitments of the permutation argument, which halo2 splits into sets of `degree - 2` columns.
+pub(crate) fn num_permutation_sets<F: Field>(cs: &ConstraintSystem<F>) -> usize {
+    let chunk_len = cs.degree() - 2;
+    (cs.permutation().get_columns().len() + chunk_len - 1) / chunk_len
+}
+
+fn constraint_system<C: Circuit<Fr>>(_: &C) -> ConstraintSystem<Fr> {
+    let mut cs = ConstraintSystem::default();
+    C::configure(&mut cs);
+    cs
+}
+
+/// Estimated size in bytes of a SHPLONK proof over BN254, following the order in which halo2 writes to the transcript.
+///
+/// Every commitment is a compressed G1 point and every evaluation a field element, both 32 bytes.
+pub fn estimated_proof_size<F: Field>(cs: &ConstraintSystem<F>) -> usize {
+    const POINT_SIZE: usize = 32;
+    const SCALAR_SIZE: usize = 32;
+
+    let num_lookups = cs.lookups().len();
+    let num_permutation_columns = cs.permutation().get_columns().len();
+    let num_permutation_sets = num_permutation_sets(cs);
+    let num_quotient_pieces = cs.degree() - 1;
+
+    let num_commitments = cs.num_advice_columns()
+        // permuted input, permuted table and product commitments
+        + 3 * num_lookups
+        + num_permutation_sets
+        // random polynomial and quotient pieces of the vanishing argument
+        + 1
+        + num_quotient_pieces
+        // SHPLONK opening proof
+        + 2;
+    let num_evaluations = cs.advice_queries().len()
+        + cs.fixed_queries().len()
+        + num_permutation_columns
+        // z(x) and z(wx) for every set, plus z(w^last x) for every set but the last
+        + (3 * num_permutation_sets).saturating_sub(1)
+        + 5 * num_lookups
+        // random polynomial of the vanishing argument
+        + 1;
+    num_commitments * POINT_SIZE + num_evaluations * SCALAR_SIZE
+}
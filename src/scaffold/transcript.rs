@@ -0,0 +1,229 @@
+//! The hash used for the Fiat-Shamir transcript of a proof, selected with `--transcript`.
+//!
+//! A proof can only be verified with the transcript it was created with:
+//! - `poseidon` is cheap to verify in a circuit, so only these snarks can be aggregated.
+//! - `blake2b` is halo2's native transcript, as used in `examples/standard_plonk.rs`.
+//! - `keccak` is the transcript of the Solidity verifier created with `--create-contract`.
+use clap::ValueEnum;
+use halo2_base::halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fr, G1Affine},
+    plonk::{create_proof, verify_proof, Circuit, Error, ProvingKey, VerifyingKey},
+    poly::{
+        commitment::{ParamsProver, Prover, Verifier},
+        kzg::{
+            commitment::{KZGCommitmentScheme, ParamsKZG},
+            multiopen::{ProverGWC, ProverSHPLONK, VerifierGWC, VerifierSHPLONK},
+            strategy::SingleStrategy,
+        },
+        VerificationStrategy,
+    },
+    transcript::{
+        Blake2bRead, Blake2bWrite, Challenge255, EncodedChallenge, TranscriptReadBuffer,
+        TranscriptWriterBuffer,
+    },
+};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use snark_verifier_sdk::{
+    halo2::PoseidonTranscript,
+    snark_verifier::system::halo2::transcript::evm::{ChallengeEvm, EvmTranscript},
+    NativeLoader,
+};
+
+use super::{
+    error::ScaffoldError,
+    multiopen::MultiOpenKind,
+    progress::{ObservedTranscript, ProveProgress, TranscriptLayout},
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptKind {
+    #[default]
+    Poseidon,
+    Blake2b,
+    Keccak,
+}
+
+impl std::fmt::Display for TranscriptKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Poseidon => write!(f, "poseidon"),
+            Self::Blake2b => write!(f, "blake2b"),
+            Self::Keccak => write!(f, "keccak"),
+        }
+    }
+}
+
+/// Creates a proof of `circuit` with the given transcript and multiopen scheme.
+///
+/// The stages of `create_proof` are reported to `progress`, which can also cancel the proof.
+pub fn gen_proof<C: Circuit<Fr>>(
+    params: &ParamsKZG<Bn256>,
+    pk: &ProvingKey<G1Affine>,
+    circuit: C,
+    instances: &[Vec<Fr>],
+    transcript: TranscriptKind,
+    multiopen: MultiOpenKind,
+    progress: &mut ProveProgress<'_>,
+) -> Result<Vec<u8>, ScaffoldError> {
+    match multiopen {
+        MultiOpenKind::Shplonk => gen_proof_with::<ProverSHPLONK<'_, Bn256>, _>(
+            params, pk, circuit, instances, transcript, progress,
+        ),
+        MultiOpenKind::Gwc => gen_proof_with::<ProverGWC<'_, Bn256>, _>(
+            params, pk, circuit, instances, transcript, progress,
+        ),
+    }
+}
+
+fn gen_proof_with<'params, P, C>(
+    params: &'params ParamsKZG<Bn256>,
+    pk: &ProvingKey<G1Affine>,
+    circuit: C,
+    instances: &[Vec<Fr>],
+    transcript: TranscriptKind,
+    progress: &mut ProveProgress<'_>,
+) -> Result<Vec<u8>, ScaffoldError>
+where
+    P: Prover<'params, KZGCommitmentScheme<Bn256>>,
+    C: Circuit<Fr>,
+{
+    match transcript {
+        TranscriptKind::Poseidon => {
+            let mut transcript = PoseidonTranscript::<NativeLoader, _>::init(vec![]);
+            create_proof_with::<P, _, _, _>(
+                params,
+                pk,
+                circuit,
+                instances,
+                &mut transcript,
+                progress,
+            )?;
+            Ok(transcript.finalize())
+        }
+        TranscriptKind::Blake2b => {
+            let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+            create_proof_with::<P, _, _, _>(
+                params,
+                pk,
+                circuit,
+                instances,
+                &mut transcript,
+                progress,
+            )?;
+            Ok(transcript.finalize())
+        }
+        TranscriptKind::Keccak => {
+            let mut transcript = EvmTranscript::<G1Affine, NativeLoader, _, _>::init(vec![]);
+            create_proof_with::<P, _, _, _>(
+                params,
+                pk,
+                circuit,
+                instances,
+                &mut transcript,
+                progress,
+            )?;
+            Ok(transcript.finalize())
+        }
+    }
+}
+
+fn create_proof_with<'params, P, C, E, T>(
+    params: &'params ParamsKZG<Bn256>,
+    pk: &ProvingKey<G1Affine>,
+    circuit: C,
+    instances: &[Vec<Fr>],
+    transcript: &mut T,
+    progress: &mut ProveProgress<'_>,
+) -> Result<(), ScaffoldError>
+where
+    P: Prover<'params, KZGCommitmentScheme<Bn256>>,
+    C: Circuit<Fr>,
+    E: EncodedChallenge<G1Affine>,
+    T: TranscriptWriterBuffer<Vec<u8>, G1Affine, E>,
+{
+    let instances = instances.iter().map(|column| &column[..]).collect::<Vec<_>>();
+    let layout = TranscriptLayout::new(pk.get_vk().cs());
+    let result = {
+        let mut transcript = ObservedTranscript::new(transcript, progress, layout)?;
+        create_proof::<KZGCommitmentScheme<Bn256>, P, E, _, _, _>(
+            params,
+            pk,
+            &[circuit],
+            &[&instances],
+            OsRng,
+            &mut transcript,
+        )
+    };
+    result.map_err(|e| progress.proof_error(e))
+}
+
+/// Verifies a proof created with the given transcript and multiopen scheme against `vk`.
+pub fn verify_proof_bytes(
+    params: &ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    instances: &[Vec<Fr>],
+    proof: &[u8],
+    transcript: TranscriptKind,
+    multiopen: MultiOpenKind,
+) -> Result<(), Error> {
+    match multiopen {
+        MultiOpenKind::Shplonk => verify_proof_with::<VerifierSHPLONK<'_, Bn256>>(
+            params, vk, instances, proof, transcript,
+        ),
+        MultiOpenKind::Gwc => {
+            verify_proof_with::<VerifierGWC<'_, Bn256>>(params, vk, instances, proof, transcript)
+        }
+    }
+}
+
+fn verify_proof_with<'params, V>(
+    params: &'params ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    instances: &[Vec<Fr>],
+    proof: &[u8],
+    transcript: TranscriptKind,
+) -> Result<(), Error>
+where
+    V: Verifier<'params, KZGCommitmentScheme<Bn256>>,
+    SingleStrategy<'params, Bn256>:
+        VerificationStrategy<'params, KZGCommitmentScheme<Bn256>, V, Output = ()>,
+{
+    let verifier_params = params.verifier_params();
+    let strategy = SingleStrategy::new(params);
+    let instances = instances.iter().map(|column| &column[..]).collect::<Vec<_>>();
+    match transcript {
+        TranscriptKind::Poseidon => {
+            let mut transcript = PoseidonTranscript::<NativeLoader, &[u8]>::new::<0>(proof);
+            verify_proof::<KZGCommitmentScheme<Bn256>, V, _, _, SingleStrategy<'_, Bn256>>(
+                verifier_params,
+                vk,
+                strategy,
+                &[&instances],
+                &mut transcript,
+            )
+        }
+        TranscriptKind::Blake2b => {
+            let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(proof);
+            verify_proof::<
+                KZGCommitmentScheme<Bn256>,
+                V,
+                Challenge255<G1Affine>,
+                Blake2bRead<&[u8], G1Affine, Challenge255<G1Affine>>,
+                SingleStrategy<'_, Bn256>,
+            >(verifier_params, vk, strategy, &[&instances], &mut transcript)
+        }
+        TranscriptKind::Keccak => {
+            let mut transcript =
+                EvmTranscript::<G1Affine, NativeLoader, &[u8], Vec<u8>>::init(proof);
+            verify_proof::<
+                KZGCommitmentScheme<Bn256>,
+                V,
+                ChallengeEvm<G1Affine>,
+                _,
+                SingleStrategy<'_, Bn256>,
+            >(verifier_params, vk, strategy, &[&instances], &mut transcript)
+        }
+    }
+}
@@ -0,0 +1,184 @@
+//! Verification of proof bundles with the params and verifying key held in memory, see [`Verifier`].
+//!
+//! For circuits created with `run` or `run_builder`, verification only needs the artifacts written by `keygen` and `prove`.
+//! Since these circuits all have the type [`ScaffoldCircuitBuilder`] and are configured entirely by their pinning,
+//! a third party can verify proofs with just the verifying key, the pinning, the proof bundle and the SRS:
+//! neither the circuit function nor any private inputs are needed. The same holds for `run_eth` circuits, whose
+//! verifying key is read for [`super::EthVerifierCircuit`], see [`try_run_verifier_with`].
+use std::path::{Path, PathBuf};
+
+use axiom_eth::util::Halo2ConfigPinning;
+use halo2_base::halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fr, G1Affine},
+    plonk::{Circuit, VerifyingKey},
+    poly::{commitment::Params, kzg::commitment::ParamsKZG},
+};
+
+use super::{
+    bundle::{vk_digest, ProofBundle},
+    cmd::{Cli, Degree, SnarkCmd},
+    error::ScaffoldError,
+    keys::{read_key_header, read_vk},
+    multiopen::{pinned_multiopen, MultiOpenKind},
+    pinning::{read_pinning, ScaffoldConfigPinning, ScaffoldPinning},
+    srs::load_srs,
+    transcript::{verify_proof_bytes, TranscriptKind},
+    ScaffoldCircuitBuilder,
+};
+
+/// Verifies proof bundles of one circuit, with the params and verifying key held in memory.
+///
+/// This is the counterpart of [`super::prover::Prover`]: it reads and writes no files.
+pub struct Verifier {
+    name: String,
+    params: ParamsKZG<Bn256>,
+    vk: VerifyingKey<G1Affine>,
+    vk_digest: String,
+    multiopen: MultiOpenKind,
+}
+
+impl Verifier {
+    /// A verifier for the circuit `name` with the verifying key `vk`, for proofs with the multiopen scheme pinned at keygen.
+    pub fn new(
+        name: &str,
+        params: ParamsKZG<Bn256>,
+        vk: VerifyingKey<G1Affine>,
+        multiopen: MultiOpenKind,
+    ) -> Self {
+        let vk_digest = vk_digest(&vk);
+        Self { name: name.to_string(), params, vk, vk_digest, multiopen }
+    }
+
+    pub fn vk(&self) -> &VerifyingKey<G1Affine> {
+        &self.vk
+    }
+
+    /// Checks that `bundle` was created for this circuit and verifying key, then runs the verifier on it.
+    pub fn verify(&self, bundle: &ProofBundle) -> Result<(), ScaffoldError> {
+        let (params, vk, vk_digest) = (&self.params, &self.vk, &self.vk_digest);
+        verify_bundle(params, vk, vk_digest, &self.name, self.multiopen, bundle)
+    }
+}
+
+/// Checks that `bundle` was created for the circuit `name` and `vk`, whose digest is `vk_digest`, then runs the verifier on it.
+pub fn verify_bundle(
+    params: &ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    vk_digest: &str,
+    name: &str,
+    multiopen: MultiOpenKind,
+    bundle: &ProofBundle,
+) -> Result<(), ScaffoldError> {
+    bundle.check(name, params.k(), vk_digest, None, multiopen)?;
+    let transcript = bundle.header.transcript;
+    verify_proof_bytes(params, vk, &bundle.instances, &bundle.proof, transcript, multiopen)
+        .map_err(ScaffoldError::Verification)
+}
+
+/// Reads the verifying key at `vk_path` for the circuit `name` with the given pinning.
+pub fn read_scaffold_vk(
+    vk_path: impl AsRef<Path>,
+    name: &str,
+    pinning: &ScaffoldConfigPinning,
+) -> Result<VerifyingKey<G1Affine>, ScaffoldError> {
+    read_pinned_vk::<ScaffoldCircuitBuilder<Fr>>(vk_path, name, pinning)
+}
+
+/// Reads the verifying key at `vk_path` for a circuit of type `C` named `name`.
+///
+/// `C::configure` must read its whole configuration from what `pinning.set_var()` sets, as
+/// [`ScaffoldCircuitBuilder`] and [`super::EthScaffoldCircuit`] do, so that no circuit has to be built.
+pub fn read_pinned_vk<C: Circuit<Fr>>(
+    vk_path: impl AsRef<Path>,
+    name: &str,
+    pinning: &impl Halo2ConfigPinning,
+) -> Result<VerifyingKey<G1Affine>, ScaffoldError> {
+    // `C::configure` reads the configuration set here
+    pinning.set_var();
+    read_vk::<G1Affine, C>(vk_path, name, pinning.degree())
+}
+
+/// Verifies `bundle` for the circuit `name` against the verifying key at `vk_path`, without rebuilding the circuit.
+///
+/// The bundle header is checked against the verifying key and the pinned multiopen scheme `multiopen`,
+/// and against `transcript` if given, before running the verifier.
+pub fn verify_standalone(
+    params: &ParamsKZG<Bn256>,
+    vk_path: impl AsRef<Path>,
+    pinning: &ScaffoldConfigPinning,
+    name: &str,
+    bundle: &ProofBundle,
+    transcript: Option<TranscriptKind>,
+    multiopen: MultiOpenKind,
+) -> Result<(), ScaffoldError> {
+    verify_pinned::<ScaffoldCircuitBuilder<Fr>>(
+        params, vk_path, pinning, name, bundle, transcript, multiopen,
+    )
+}
+
+/// Same as [`verify_standalone`], for a circuit of type `C` with any pinning, see [`read_pinned_vk`].
+pub fn verify_pinned<C: Circuit<Fr>>(
+    params: &ParamsKZG<Bn256>,
+    vk_path: impl AsRef<Path>,
+    pinning: &impl Halo2ConfigPinning,
+    name: &str,
+    bundle: &ProofBundle,
+    transcript: Option<TranscriptKind>,
+    multiopen: MultiOpenKind,
+) -> Result<(), ScaffoldError> {
+    let vk_digest = read_key_header(&vk_path)?.vk_digest;
+    bundle.check(name, params.k(), &vk_digest, transcript, multiopen)?;
+    let vk = read_pinned_vk::<C>(vk_path, name, pinning)?;
+    let transcript = bundle.header.transcript;
+    verify_proof_bytes(params, &vk, &bundle.instances, &bundle.proof, transcript, multiopen)
+        .map_err(ScaffoldError::Verification)
+}
+
+/// Runs `verify` from the command line using only `configs/<name>.json`, `data/<name>.vk` and `data/<name>.bundle`.
+///
+/// Exits the process with [`ScaffoldError::exit_code`] on failure; see [`try_run_verifier`] for a variant returning the error.
+pub fn run_verifier(cli: Cli) {
+    try_run_verifier(cli).unwrap_or_else(|e| e.exit())
+}
+
+pub fn try_run_verifier(cli: Cli) -> Result<(), ScaffoldError> {
+    try_run_verifier_with::<ScaffoldCircuitBuilder<Fr>, ScaffoldConfigPinning>(cli)
+}
+
+/// Same as [`try_run_verifier`], for circuits of type `C` pinned with `P`, see [`read_pinned_vk`].
+///
+/// `run_eth` uses this with [`super::EthVerifierCircuit`] and [`super::pinning::EthScaffoldPinning`].
+pub fn try_run_verifier_with<C, P>(cli: Cli) -> Result<(), ScaffoldError>
+where
+    C: Circuit<Fr>,
+    P: ScaffoldPinning,
+{
+    if !matches!(cli.command, SnarkCmd::Verify) {
+        return Err(ScaffoldError::Config(
+            "only verify can run without the circuit function".to_string(),
+        ));
+    }
+    let name = &cli.name;
+    let config_path = cli.config_path.clone().unwrap_or_else(|| PathBuf::from("configs"));
+    let data_path = cli.data_path.clone().unwrap_or_else(|| PathBuf::from("data"));
+
+    let pinning_path = config_path.join(PathBuf::from(format!("{name}.json")));
+    let pinning: P = read_pinning(&pinning_path)?;
+    pinning.check_matches(cli.lookup_bits, cli.minimum_rows)?;
+    let multiopen = pinned_multiopen(&pinning_path, cli.multiopen)?;
+    let k = match cli.degree {
+        Degree::Fixed(k) => k,
+        Degree::Auto => pinning.degree(),
+    };
+    pinning.check_degree(k)?;
+    let vk_path = data_path.join(PathBuf::from(format!("{name}.vk")));
+    let bundle_path = data_path.join(PathBuf::from(format!("{name}.bundle")));
+    let bundle = ProofBundle::read(&bundle_path)?;
+    // fail fast on a mismatched bundle, before loading the SRS
+    bundle.check(name, k, &read_key_header(&vk_path)?.vk_digest, cli.transcript, multiopen)?;
+
+    let params = load_srs(&cli, k)?;
+    verify_pinned::<C>(&params, vk_path, &pinning, name, &bundle, cli.transcript, multiopen)?;
+    println!("Snark verified successfully!");
+    Ok(())
+}
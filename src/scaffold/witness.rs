@@ -0,0 +1,16 @@
+//! The `witness` command: a dry run of the circuit function that only computes its outputs.
+//!
+//! The function runs with a witness-only `GateThreadBuilder`, as in the prover stage, so no selectors or copy
+//! constraints are recorded and no circuit is configured or synthesized. Nothing is checked: use `mock` for that.
+use serde::{Deserialize, Serialize};
+
+use super::instances::NamedInstance;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WitnessReport {
+    /// Total number of advice cells over all phases and threads
+    pub advice_cells: usize,
+    /// Total number of advice cells that are range checked with the lookup table
+    pub lookup_cells: usize,
+    pub instances: Vec<NamedInstance>,
+}